@@ -2,18 +2,20 @@ use native_dialog::*;
 
 fn echo<T: std::fmt::Debug>(name: &str, value: &T) {
     let dialog = MessageAlert {
-        title: "Result",
+        title: Some("Result"),
         text: &format!("{}: {:?}", &name, &value),
         typ: MessageType::Info,
+        ..Default::default()
     };
     dialog.show().unwrap();
 }
 
 fn main() {
     let dialog = MessageConfirm {
-        title: "Tour",
+        title: Some("Tour"),
         text: "Let's begin the tour!",
         typ: MessageType::Info,
+        ..Default::default()
     };
     let result = dialog.show().unwrap();
     if !result {
@@ -24,6 +26,7 @@ fn main() {
     let dialog = OpenSingleFile {
         dir: None,
         filter: None,
+        ..Default::default()
     };
     let result = dialog.show().unwrap();
     echo("OpenSingleFile", &result);
@@ -31,18 +34,46 @@ fn main() {
     let dialog = OpenMultipleFile {
         dir: None,
         filter: None,
+        ..Default::default()
     };
     let result = dialog.show().unwrap();
     echo("OpenMultipleFile", &result);
 
-    let dialog = OpenSingleDir { dir: None };
+    let dialog = OpenSingleDir {
+        dir: None,
+        ..Default::default()
+    };
     let result = dialog.show().unwrap();
     echo("OpenSingleDir", &result);
 
+    let dialog = SaveFile {
+        dir: None,
+        name: "tour.txt",
+        confirm_overwrite: true,
+        ..Default::default()
+    };
+    let result = dialog.show().unwrap();
+    echo("SaveFile", &result);
+
+    let dialog = InputText {
+        title: "Tour",
+        text: "What is your name?",
+        ..Default::default()
+    };
+    let result = dialog
+        .validate(|name| match name.is_empty() {
+            true => Err("The name cannot be empty".into()),
+            false => Ok(()),
+        })
+        .show()
+        .unwrap();
+    echo("InputText", &result);
+
     let dialog = MessageAlert {
-        title: "End",
+        title: Some("End"),
         text: "That's the end!",
         typ: MessageType::Info,
+        ..Default::default()
     };
     dialog.show().unwrap();
 }
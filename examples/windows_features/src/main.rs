@@ -4,15 +4,17 @@ fn main() {
     let dialog = OpenSingleFile {
         dir: None,
         filter: None,
+        ..Default::default()
     };
     let result = dialog.show();
 
     let message = format!("Shit is on fire!\n\n{:?}", result);
 
     let dialog = MessageConfirm {
-        title: "What is happening?",
+        title: Some("What is happening?"),
         text: &message,
         typ: MessageType::Info,
+        ..Default::default()
     };
     let result = dialog.show();
 
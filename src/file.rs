@@ -1,18 +1,1473 @@
+use crate::{Dialog, Error, NativeDialogHandle, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+#[cfg(not(target_os = "macos"))]
+use std::{collections::BTreeMap, sync::Mutex};
+
+#[derive(Clone, Copy)]
+pub struct FileFilter<'a> {
+    pub name: &'a str,
+    pub extensions: &'a [&'a str],
+}
+
+#[derive(Default)]
 pub struct OpenSingleFile<'a> {
     pub dir: Option<&'a str>,
+    pub location_url: Option<&'a str>,
+    pub known_folder: Option<KnownFolder>,
     pub filter: Option<&'a [&'a str]>,
+    pub filters: Vec<FileFilter<'a>>,
+    pub mime_filters: Vec<(&'a str, Vec<&'a str>)>,
+    pub merge_filters_default: bool,
+    pub case_sensitive_filter: bool,
+    pub remember_filter: Option<&'a str>,
+    pub show_package_contents: bool,
+    pub resolve_shortcuts: Option<bool>,
+    pub create_dir_if_missing: bool,
+    pub auto_select_single: bool,
+    pub persistence_key: Option<u128>,
+    pub autosave_position: Option<&'a str>,
+    pub default_view: Option<FileDialogView>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
+    pub child_cwd: Option<&'a Path>,
+    pub configure_native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
 }
 
+#[derive(Default)]
 pub struct OpenMultipleFile<'a> {
     pub dir: Option<&'a str>,
+    pub location_url: Option<&'a str>,
+    pub known_folder: Option<KnownFolder>,
     pub filter: Option<&'a [&'a str]>,
+    pub filters: Vec<FileFilter<'a>>,
+    pub mime_filters: Vec<(&'a str, Vec<&'a str>)>,
+    pub merge_filters_default: bool,
+    pub case_sensitive_filter: bool,
+    pub remember_filter: Option<&'a str>,
+    pub max_selection: Option<usize>,
+    pub truncate_selection: bool,
+    pub sort: SortOrder,
+    pub show_package_contents: bool,
+    pub resolve_shortcuts: Option<bool>,
+    pub create_dir_if_missing: bool,
+    pub persistence_key: Option<u128>,
+    pub autosave_position: Option<&'a str>,
+    pub default_view: Option<FileDialogView>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
+    pub child_cwd: Option<&'a Path>,
+    pub configure_native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
 }
 
+#[derive(Default)]
 pub struct OpenSingleDir<'a> {
     pub dir: Option<&'a str>,
+    pub location_url: Option<&'a str>,
+    pub known_folder: Option<KnownFolder>,
+    pub show_package_contents: bool,
+    pub create_dir_if_missing: bool,
+    pub persistence_key: Option<u128>,
+    pub autosave_position: Option<&'a str>,
+    pub default_view: Option<FileDialogView>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
+    pub child_cwd: Option<&'a Path>,
+    pub configure_native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
+}
+
+/// Lets the user pick either a file or a folder. Only macOS can offer both at once; the other
+/// backends fall back to picking a file.
+#[derive(Default)]
+pub struct OpenSingleItem<'a> {
+    pub dir: Option<&'a str>,
+    pub location_url: Option<&'a str>,
+    pub known_folder: Option<KnownFolder>,
+    pub filter: Option<&'a [&'a str]>,
+    pub filters: Vec<FileFilter<'a>>,
+    pub mime_filters: Vec<(&'a str, Vec<&'a str>)>,
+    pub merge_filters_default: bool,
+    pub case_sensitive_filter: bool,
+    pub show_package_contents: bool,
+    pub resolve_shortcuts: Option<bool>,
+    pub create_dir_if_missing: bool,
+    pub persistence_key: Option<u128>,
+    pub autosave_position: Option<&'a str>,
+    pub join_all_spaces: Option<bool>,
+    pub default_view: Option<FileDialogView>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
+    pub child_cwd: Option<&'a Path>,
+    pub configure_native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
+}
+
+/// Backends don't agree on the order of the chosen files, some keep the order they were selected in
+/// and others sort them. `Unsorted` leaves them the way the backend returned them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SortOrder {
+    #[default]
+    Unsorted,
+    FileName,
+    Path,
+}
+
+/// Windows resolves these through the shell, Linux through the XDG user directories, and macOS
+/// takes the folders of the same name in the home folder, with "Movies" for `Videos`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KnownFolder {
+    Home,
+    Desktop,
+    Documents,
+    Downloads,
+    Music,
+    Pictures,
+    Videos,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FileDialogView {
+    Details,
+    List,
+    Tiles,
+    Icons,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ItemKind {
+    File,
+    Directory,
 }
 
+/// A rough idea of what a file holds, going by its extension alone. It's looked up in the same
+/// table `add_mime_filter` uses, so a file that passes an "image/*" filter is an `Image`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FileKind {
+    Image,
+    Audio,
+    Video,
+    Text,
+    Archive,
+    Other,
+}
+
+impl FileKind {
+    pub fn from_path(path: &Path) -> FileKind {
+        let extension = match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => extension,
+            None => return FileKind::Other,
+        };
+
+        let mime_type = MIME_EXTENSIONS.iter().find(|(_, extensions)| {
+            extensions
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(extension))
+        });
+
+        match mime_type.map(|(mime_type, _)| mime_type.split_once('/')) {
+            Some(Some(("image", _))) => FileKind::Image,
+            Some(Some(("audio", _))) => FileKind::Audio,
+            Some(Some(("video", _))) => FileKind::Video,
+            Some(Some(("text", _))) | Some(Some(("application", "json" | "xml"))) => FileKind::Text,
+            Some(Some(("application", archive))) if ARCHIVE_TYPES.contains(&archive) => {
+                FileKind::Archive
+            }
+            _ => FileKind::Other,
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct SaveFile<'a> {
     pub dir: Option<&'a str>,
+    pub location_url: Option<&'a str>,
+    pub known_folder: Option<KnownFolder>,
     pub name: &'a str,
+    pub filters: Vec<FileFilter<'a>>,
+    pub mime_filters: Vec<(&'a str, Vec<&'a str>)>,
+    pub default_filter: usize,
+    pub confirm_overwrite: bool,
+    pub unique_name: bool,
+    pub strict_extension: Option<bool>,
+    pub case_sensitive_filter: bool,
+    pub create_dir_if_missing: bool,
+    pub persistence_key: Option<u128>,
+    pub autosave_position: Option<&'a str>,
+    pub default_view: Option<FileDialogView>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
+    pub child_cwd: Option<&'a Path>,
+    pub configure_native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
+}
+
+/// Asks whether to open an existing file or create a new one, then shows the matching dialog:
+/// `OpenSingleFile` for "Open…" and `SaveFile` for "Create New…", both starting in `dir` with
+/// `filters`. Cancelling either step gives `OpenOrCreateChoice::Cancel`. With the `mock` feature
+/// the question takes `Confirm(true)` for "Open…", `Confirm(false)` for "Create New…" or `Cancel`,
+/// and each dialog after it takes its own response.
+#[derive(Default)]
+pub struct OpenOrCreate<'a> {
+    pub title: Option<&'a str>,
+    pub text: &'a str,
+    pub dir: Option<&'a str>,
+    pub name: &'a str,
+    pub filters: Vec<FileFilter<'a>>,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OpenOrCreateChoice {
+    Open(PathBuf),
+    Create(PathBuf),
+    Cancel,
+}
+
+impl<'a> OpenSingleFile<'a> {
+    pub fn add_filter(mut self, name: &'a str, extensions: &'a [&'a str]) -> Self {
+        self.filters.push(FileFilter { name, extensions });
+        self
+    }
+
+    /// Turns MIME types such as "application/pdf" or "image/*" into extensions from a short
+    /// built-in table. The groups come after the ones from `add_filter`, and a group with no
+    /// known MIME types is left out.
+    pub fn add_mime_filter(mut self, name: &'a str, mime_types: &[&str]) -> Self {
+        push_mime_filter(&mut self.mime_filters, name, mime_types);
+        self
+    }
+
+    /// Puts an "All supported files" group ahead of the others, with every extension from all of
+    /// them, so the dialog starts out showing any file that one of the groups would. It's only
+    /// added when there are at least two groups to merge, and it counts as the first filter
+    /// wherever filters are picked or remembered by index.
+    pub fn merge_filters_default(mut self, merge: bool) -> Self {
+        self.merge_filters_default = merge;
+        self
+    }
+
+    /// Extensions match whatever their case, so "png" matches "photo.PNG" as well. Turning this on
+    /// has them match only as given, for tools that tell ".C" files from ".c" ones. Only zenity and
+    /// kdialog can match either way; Windows and macOS always ignore the case of extensions.
+    pub fn case_sensitive_filter(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive_filter = case_sensitive;
+        self
+    }
+
+    /// Starts on the filter group that was last picked under `key`, and remembers the one picked
+    /// this time. The choice is kept in memory for as long as the process runs, shared by every
+    /// dialog given the same key. macOS doesn't offer filter groups, so there's nothing to
+    /// remember there.
+    pub fn remember_filter(mut self, key: &'a str) -> Self {
+        self.remember_filter = Some(key);
+        self
+    }
+
+    /// When the starting folder holds exactly one file that the filters match, that file is
+    /// returned without showing a dialog, so `show` no longer always shows one. Only the folder
+    /// itself is looked at, and without any filters every file matches. With none or several, or
+    /// a folder that can't be read, the dialog is shown as usual.
+    pub fn auto_select_single(mut self, auto_select: bool) -> Self {
+        self.auto_select_single = auto_select;
+        self
+    }
+
+    /// Windows keeps the window position under a client GUID made from the key, unless
+    /// `persistence_key` already gives one. On macOS only `OpenSingleItem` uses a panel that can
+    /// autosave its frame, and zenity and kdialog can't place their windows at all.
+    pub fn autosave_position(mut self, key: &'a str) -> Self {
+        self.autosave_position = Some(key);
+        self
+    }
+
+    /// The client GUID Windows keeps the dialog's last folder, view and size under, so that
+    /// dialogs used for different things don't share them. It does nothing on other platforms.
+    pub fn persistence_key(mut self, guid: u128) -> Self {
+        self.persistence_key = Some(guid);
+        self
+    }
+
+    /// Starts in one of the user's standard folders, wherever they've moved it to. `dir` and
+    /// `location_url` take precedence when they're set, and a folder that can't be found leaves the
+    /// dialog to open where it would have without one.
+    pub fn set_known_folder(mut self, folder: KnownFolder) -> Self {
+        self.known_folder = Some(folder);
+        self
+    }
+
+    /// Starts where a `file://` URL points, ahead of `dir` and the known folder. macOS hands the
+    /// URL to the panel as it is, which is how iCloud and network locations are reached there, and
+    /// the other platforms turn it into a path. Any other scheme fails with `Error::UnsupportedUrl`
+    /// before a dialog is shown.
+    pub fn set_location_url(mut self, url: &'a str) -> Self {
+        self.location_url = Some(url);
+        self
+    }
+
+    /// Creates the starting folder, along with any missing parents, before the dialog is shown.
+    /// Failing to create it fails the dialog with the I/O error, rather than opening somewhere
+    /// else.
+    pub fn create_dir_if_missing(mut self, create: bool) -> Self {
+        self.create_dir_if_missing = create;
+        self
+    }
+
+    /// Lets app bundles and document packages such as `.app` and `.rtfd` be picked as single
+    /// items on macOS, rather than opened like folders. That's the panel's default; `false` lets
+    /// the user browse inside them. Other platforms have no packages, so it does nothing there.
+    pub fn treat_packages_as_files(mut self, treat_as_files: bool) -> Self {
+        self.show_package_contents = !treat_as_files;
+        self
+    }
+
+    /// Whether a picked shortcut is followed to its target: a `.lnk` file on Windows, a symlink
+    /// elsewhere. Left unset, Windows follows them and the other platforms leave them as picked.
+    pub fn resolve_shortcuts(mut self, resolve: bool) -> Self {
+        self.resolve_shortcuts = Some(resolve);
+        self
+    }
+
+    /// Only Windows lets the view be picked. Without one, it comes back the way the user last left
+    /// it for the same `persistence_key`, if any.
+    pub fn default_view(mut self, view: FileDialogView) -> Self {
+        self.default_view = Some(view);
+        self
+    }
+
+    /// Same as `MessageAlert::retry`, only zenity and kdialog are retried.
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+
+    /// Closes the dialog when it hasn't been answered in time, and fails with `Error::Timeout`.
+    /// Only zenity and kdialog can be closed from outside, so it does nothing on Windows and macOS.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The working directory zenity or kdialog runs in. Only the Linux backend runs the dialog as
+    /// a child process, so it does nothing on the other platforms. A relative `dir` is taken from
+    /// the process's own working directory as it is when the dialog is shown, not from this one.
+    pub fn child_cwd(mut self, dir: &'a Path) -> Self {
+        self.child_cwd = Some(dir);
+        self
+    }
+
+    /// Unstable. Called with the platform's dialog before it's shown, see `NativeDialogHandle`.
+    pub fn configure_native(mut self, configure: &'a (dyn Fn(NativeDialogHandle) + Sync)) -> Self {
+        self.configure_native = Some(configure);
+        self
+    }
+}
+
+impl<'a> OpenMultipleFile<'a> {
+    pub fn add_filter(mut self, name: &'a str, extensions: &'a [&'a str]) -> Self {
+        self.filters.push(FileFilter { name, extensions });
+        self
+    }
+
+    pub fn add_mime_filter(mut self, name: &'a str, mime_types: &[&str]) -> Self {
+        push_mime_filter(&mut self.mime_filters, name, mime_types);
+        self
+    }
+
+    pub fn merge_filters_default(mut self, merge: bool) -> Self {
+        self.merge_filters_default = merge;
+        self
+    }
+
+    pub fn case_sensitive_filter(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive_filter = case_sensitive;
+        self
+    }
+
+    pub fn remember_filter(mut self, key: &'a str) -> Self {
+        self.remember_filter = Some(key);
+        self
+    }
+
+    pub fn sort_results(mut self, order: SortOrder) -> Self {
+        self.sort = order;
+        self
+    }
+
+    /// None of the backends can limit the selection while the dialog is open, so picking more
+    /// files than this fails with `Error::TooManySelected` once it closes, unless
+    /// `truncate_selection` is set.
+    pub fn max_selection(mut self, max: usize) -> Self {
+        self.max_selection = Some(max);
+        self
+    }
+
+    /// Keeps the first `max_selection` files in `sort_results` order instead of failing.
+    pub fn truncate_selection(mut self, truncate: bool) -> Self {
+        self.truncate_selection = truncate;
+        self
+    }
+
+    pub fn autosave_position(mut self, key: &'a str) -> Self {
+        self.autosave_position = Some(key);
+        self
+    }
+
+    pub fn persistence_key(mut self, guid: u128) -> Self {
+        self.persistence_key = Some(guid);
+        self
+    }
+
+    pub fn set_known_folder(mut self, folder: KnownFolder) -> Self {
+        self.known_folder = Some(folder);
+        self
+    }
+
+    pub fn set_location_url(mut self, url: &'a str) -> Self {
+        self.location_url = Some(url);
+        self
+    }
+
+    pub fn create_dir_if_missing(mut self, create: bool) -> Self {
+        self.create_dir_if_missing = create;
+        self
+    }
+
+    pub fn treat_packages_as_files(mut self, treat_as_files: bool) -> Self {
+        self.show_package_contents = !treat_as_files;
+        self
+    }
+
+    pub fn resolve_shortcuts(mut self, resolve: bool) -> Self {
+        self.resolve_shortcuts = Some(resolve);
+        self
+    }
+
+    pub fn default_view(mut self, view: FileDialogView) -> Self {
+        self.default_view = Some(view);
+        self
+    }
+
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn child_cwd(mut self, dir: &'a Path) -> Self {
+        self.child_cwd = Some(dir);
+        self
+    }
+
+    /// Unstable. Called with the platform's dialog before it's shown, see `NativeDialogHandle`.
+    pub fn configure_native(mut self, configure: &'a (dyn Fn(NativeDialogHandle) + Sync)) -> Self {
+        self.configure_native = Some(configure);
+        self
+    }
+}
+
+impl<'a> OpenSingleItem<'a> {
+    pub fn add_filter(mut self, name: &'a str, extensions: &'a [&'a str]) -> Self {
+        self.filters.push(FileFilter { name, extensions });
+        self
+    }
+
+    pub fn add_mime_filter(mut self, name: &'a str, mime_types: &[&str]) -> Self {
+        push_mime_filter(&mut self.mime_filters, name, mime_types);
+        self
+    }
+
+    pub fn merge_filters_default(mut self, merge: bool) -> Self {
+        self.merge_filters_default = merge;
+        self
+    }
+
+    pub fn case_sensitive_filter(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive_filter = case_sensitive;
+        self
+    }
+
+    pub fn autosave_position(mut self, key: &'a str) -> Self {
+        self.autosave_position = Some(key);
+        self
+    }
+
+    pub fn persistence_key(mut self, guid: u128) -> Self {
+        self.persistence_key = Some(guid);
+        self
+    }
+
+    /// Same as `TextInfo::join_all_spaces`.
+    pub fn join_all_spaces(mut self, join: bool) -> Self {
+        self.join_all_spaces = Some(join);
+        self
+    }
+
+    pub fn set_known_folder(mut self, folder: KnownFolder) -> Self {
+        self.known_folder = Some(folder);
+        self
+    }
+
+    pub fn set_location_url(mut self, url: &'a str) -> Self {
+        self.location_url = Some(url);
+        self
+    }
+
+    pub fn create_dir_if_missing(mut self, create: bool) -> Self {
+        self.create_dir_if_missing = create;
+        self
+    }
+
+    pub fn treat_packages_as_files(mut self, treat_as_files: bool) -> Self {
+        self.show_package_contents = !treat_as_files;
+        self
+    }
+
+    pub fn resolve_shortcuts(mut self, resolve: bool) -> Self {
+        self.resolve_shortcuts = Some(resolve);
+        self
+    }
+
+    pub fn default_view(mut self, view: FileDialogView) -> Self {
+        self.default_view = Some(view);
+        self
+    }
+
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn child_cwd(mut self, dir: &'a Path) -> Self {
+        self.child_cwd = Some(dir);
+        self
+    }
+
+    /// Unstable. Called with the platform's dialog before it's shown, see `NativeDialogHandle`.
+    pub fn configure_native(mut self, configure: &'a (dyn Fn(NativeDialogHandle) + Sync)) -> Self {
+        self.configure_native = Some(configure);
+        self
+    }
+}
+
+impl<'a> OpenSingleDir<'a> {
+    pub fn autosave_position(mut self, key: &'a str) -> Self {
+        self.autosave_position = Some(key);
+        self
+    }
+
+    pub fn persistence_key(mut self, guid: u128) -> Self {
+        self.persistence_key = Some(guid);
+        self
+    }
+
+    pub fn set_known_folder(mut self, folder: KnownFolder) -> Self {
+        self.known_folder = Some(folder);
+        self
+    }
+
+    pub fn set_location_url(mut self, url: &'a str) -> Self {
+        self.location_url = Some(url);
+        self
+    }
+
+    pub fn create_dir_if_missing(mut self, create: bool) -> Self {
+        self.create_dir_if_missing = create;
+        self
+    }
+
+    pub fn treat_packages_as_files(mut self, treat_as_files: bool) -> Self {
+        self.show_package_contents = !treat_as_files;
+        self
+    }
+
+    pub fn default_view(mut self, view: FileDialogView) -> Self {
+        self.default_view = Some(view);
+        self
+    }
+
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn child_cwd(mut self, dir: &'a Path) -> Self {
+        self.child_cwd = Some(dir);
+        self
+    }
+
+    /// Unstable. Called with the platform's dialog before it's shown, see `NativeDialogHandle`.
+    pub fn configure_native(mut self, configure: &'a (dyn Fn(NativeDialogHandle) + Sync)) -> Self {
+        self.configure_native = Some(configure);
+        self
+    }
+}
+
+impl<'a> SaveFile<'a> {
+    pub fn add_filter(mut self, name: &'a str, extensions: &'a [&'a str]) -> Self {
+        self.filters.push(FileFilter { name, extensions });
+        self
+    }
+
+    pub fn add_mime_filter(mut self, name: &'a str, mime_types: &[&str]) -> Self {
+        push_mime_filter(&mut self.mime_filters, name, mime_types);
+        self
+    }
+
+    /// Asks before returning the path of a file that already exists, and brings the dialog back
+    /// when the user declines. macOS's save panel and kdialog always ask on their own and can't be
+    /// told not to, so `false` doesn't stop them asking.
+    pub fn confirm_overwrite(mut self, confirm: bool) -> Self {
+        self.confirm_overwrite = confirm;
+        self
+    }
+
+    /// Picks the filter group the dialog starts on. Windows then adds its first extension to names
+    /// typed without one, and zenity and kdialog do the same after the dialog closes. macOS can't
+    /// offer filters when saving.
+    pub fn default_filter_index(mut self, index: usize) -> Self {
+        self.default_filter = index;
+        self
+    }
+
+    /// Starts with "name (1).ext", "name (2).ext" and so on when `name` is already taken in the
+    /// starting folder. Without a starting folder the name is left as it is.
+    pub fn unique_name(mut self, unique: bool) -> Self {
+        self.unique_name = unique;
+        self
+    }
+
+    /// Left unset, Windows, zenity and kdialog add the filter's first extension to names typed
+    /// without one and keep any extension that was typed, while macOS keeps the name as it was
+    /// typed. `true` swaps an extension the filter doesn't list for its first one, so "image.jpg"
+    /// comes back as "image.png". `false` hands back the name exactly as it was typed. On Windows
+    /// the filter is the one picked in the dialog, elsewhere it's the default one.
+    pub fn strict_extension(mut self, strict: bool) -> Self {
+        self.strict_extension = Some(strict);
+        self
+    }
+
+    pub fn case_sensitive_filter(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive_filter = case_sensitive;
+        self
+    }
+
+    pub fn autosave_position(mut self, key: &'a str) -> Self {
+        self.autosave_position = Some(key);
+        self
+    }
+
+    pub fn persistence_key(mut self, guid: u128) -> Self {
+        self.persistence_key = Some(guid);
+        self
+    }
+
+    pub fn set_known_folder(mut self, folder: KnownFolder) -> Self {
+        self.known_folder = Some(folder);
+        self
+    }
+
+    pub fn set_location_url(mut self, url: &'a str) -> Self {
+        self.location_url = Some(url);
+        self
+    }
+
+    pub fn create_dir_if_missing(mut self, create: bool) -> Self {
+        self.create_dir_if_missing = create;
+        self
+    }
+
+    pub fn default_view(mut self, view: FileDialogView) -> Self {
+        self.default_view = Some(view);
+        self
+    }
+
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn child_cwd(mut self, dir: &'a Path) -> Self {
+        self.child_cwd = Some(dir);
+        self
+    }
+
+    /// Unstable. Called with the platform's dialog before it's shown, see `NativeDialogHandle`.
+    pub fn configure_native(mut self, configure: &'a (dyn Fn(NativeDialogHandle) + Sync)) -> Self {
+        self.configure_native = Some(configure);
+        self
+    }
+}
+
+impl<'a> OpenOrCreate<'a> {
+    pub fn add_filter(mut self, name: &'a str, extensions: &'a [&'a str]) -> Self {
+        self.filters.push(FileFilter { name, extensions });
+        self
+    }
+}
+
+impl Dialog for OpenOrCreate<'_> {
+    type Output = OpenOrCreateChoice;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        let question = crate::message::AskOpenOrCreate {
+            title: self.title,
+            text: match self.text {
+                "" => "Do you want to open an existing file or create a new one?",
+                text => text,
+            },
+            timeout: self.timeout,
+        };
+
+        match question.show()? {
+            Some(true) => {
+                let dialog = OpenSingleFile {
+                    dir: self.dir,
+                    filters: self.filters.clone(),
+                    timeout: self.timeout,
+                    ..Default::default()
+                };
+                Ok(dialog
+                    .show()?
+                    .map_or(OpenOrCreateChoice::Cancel, OpenOrCreateChoice::Open))
+            }
+            Some(false) => {
+                let dialog = SaveFile {
+                    dir: self.dir,
+                    name: self.name,
+                    filters: self.filters.clone(),
+                    timeout: self.timeout,
+                    ..Default::default()
+                };
+                Ok(dialog
+                    .show()?
+                    .map_or(OpenOrCreateChoice::Cancel, OpenOrCreateChoice::Create))
+            }
+            None => Ok(OpenOrCreateChoice::Cancel),
+        }
+    }
+}
+
+pub(crate) fn item_kind(path: PathBuf) -> (PathBuf, ItemKind) {
+    match path.is_dir() {
+        true => (path, ItemKind::Directory),
+        false => (path, ItemKind::File),
+    }
+}
+
+// The file may be gone by the time it's looked at, which leaves it without metadata rather than
+// failing the whole dialog.
+fn with_metadata(path: PathBuf) -> (PathBuf, Option<Metadata>) {
+    let metadata = std::fs::metadata(&path).ok();
+    (path, metadata)
+}
+
+impl OpenSingleFile<'_> {
+    pub fn show_with_metadata(self) -> Result<Option<(PathBuf, Option<Metadata>)>> {
+        Ok(self.show()?.map(with_metadata))
+    }
+}
+
+impl OpenMultipleFile<'_> {
+    pub fn show_with_metadata(self) -> Result<Vec<(PathBuf, Option<Metadata>)>> {
+        Ok(self.show()?.into_iter().map(with_metadata).collect())
+    }
+}
+
+/// The key `show_grouped` files the chosen files under when they match none of the filters.
+pub const UNMATCHED_FILTER: usize = usize::MAX;
+
+impl OpenMultipleFile<'_> {
+    /// Sorts the chosen files by the filter their extension matches, keyed by its index among the
+    /// filters: the unnamed `filter` first, then the ones from `add_filter`, then the ones from
+    /// `add_mime_filter`. A file matching several filters goes under the first of them. The filter
+    /// the user had selected in the dialog isn't known, so it plays no part. Neither does the
+    /// group from `merge_filters_default`, which every file would match.
+    pub fn show_grouped(self) -> Result<HashMap<usize, Vec<PathBuf>>> {
+        let paths = self.show_ref()?;
+        let filters = filter_groups(self.filter, &self.filters, &self.mime_filters, None);
+
+        let mut groups: HashMap<usize, Vec<PathBuf>> = HashMap::new();
+        for path in paths {
+            let index = matching_filter(&filters, 0, &path, self.case_sensitive_filter)
+                .unwrap_or(UNMATCHED_FILTER);
+            groups.entry(index).or_default().push(path);
+        }
+
+        Ok(groups)
+    }
+}
+
+// Shortcuts are `.lnk` files on Windows and symbolic links elsewhere. Left unset, each platform
+// does what it always has: Windows resolves them and Linux returns the link itself. macOS resolves
+// aliases and symbolic links before the path ever gets here, whatever the setting, so a link
+// resolved there isn't reported. The link is returned alongside its target when it was resolved.
+pub(crate) fn resolve_shortcut(path: PathBuf, resolve: Option<bool>) -> (PathBuf, Option<PathBuf>) {
+    if resolve != Some(true) {
+        return (path, None);
+    }
+
+    match crate::r#impl::shortcut_target(&path) {
+        Some(target) => (target, Some(path)),
+        None => (path, None),
+    }
+}
+
+impl OpenSingleFile<'_> {
+    pub(crate) fn single_match(&self) -> Option<PathBuf> {
+        if !self.auto_select_single {
+            return None;
+        }
+
+        let dir = match resolve_location(self.dir, self.location_url, self.known_folder) {
+            Ok(Some(dir)) if !dir.is_empty() => PathBuf::from(&*expand_home(&dir)),
+            _ => return None,
+        };
+        let dir = match dir.is_relative() {
+            true => std::env::current_dir().ok()?.join(dir),
+            false => dir,
+        };
+
+        let filters = filter_groups(self.filter, &self.filters, &self.mime_filters, None);
+        let mut matches = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                filters.is_empty()
+                    || matching_filter(&filters, 0, path, self.case_sensitive_filter).is_some()
+            });
+
+        match (matches.next(), matches.next()) {
+            (Some(path), None) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Pairs the chosen file with the shortcut the user picked to get to it, if one was resolved.
+    pub fn show_with_shortcuts(mut self) -> Result<Option<(PathBuf, Option<PathBuf>)>> {
+        // The shortcut is resolved by the crate rather than the dialog, so that the link is
+        // known. Unless `resolve_shortcuts` says otherwise, this keeps the platform's own choice.
+        self.resolve_shortcuts
+            .get_or_insert(cfg!(target_os = "windows"));
+        self.show_with_links()
+    }
+}
+
+impl OpenMultipleFile<'_> {
+    pub fn show_with_shortcuts(mut self) -> Result<Vec<(PathBuf, Option<PathBuf>)>> {
+        self.resolve_shortcuts
+            .get_or_insert(cfg!(target_os = "windows"));
+        self.show_with_links()
+    }
+}
+
+impl OpenSingleDir<'_> {
+    pub fn show_with_metadata(self) -> Result<Option<(PathBuf, Option<Metadata>)>> {
+        Ok(self.show()?.map(with_metadata))
+    }
+
+    /// For telling whether the folder is empty. A folder that can't be read is left without a
+    /// count, like a file without metadata.
+    pub fn show_with_entry_count(self) -> Result<Option<(PathBuf, Option<usize>)>> {
+        Ok(self.show()?.map(|path| {
+            let count = std::fs::read_dir(&path).ok().map(Iterator::count);
+            (path, count)
+        }))
+    }
+}
+
+// The size is checked up front, and the read is capped too in case the file grows meanwhile.
+fn read_text(path: PathBuf, max_size: u64) -> Result<(PathBuf, String)> {
+    use std::io::Read;
+
+    let file = std::fs::File::open(&path)?;
+
+    let size = file.metadata()?.len();
+    if size > max_size {
+        return Err(Error::FileTooLarge(size));
+    }
+
+    let mut bytes = Vec::new();
+    file.take(max_size).read_to_end(&mut bytes)?;
+    Ok((path, String::from_utf8(bytes)?))
+}
+
+impl OpenSingleFile<'_> {
+    /// Shows the dialog and reads the chosen file as UTF-8 text. Files bigger than `max_size`
+    /// bytes aren't read at all, and content that isn't UTF-8 fails with `Error::InvalidString`.
+    pub fn show_read_to_string(self, max_size: u64) -> Result<Option<(PathBuf, String)>> {
+        self.show()?
+            .map(|path| read_text(path, max_size))
+            .transpose()
+    }
+}
+
+#[cfg(feature = "memmap2")]
+fn map_file(path: PathBuf) -> Result<memmap2::Mmap> {
+    let file = std::fs::File::open(path)?;
+    Ok(unsafe { memmap2::Mmap::map(&file)? })
+}
+
+#[cfg(feature = "memmap2")]
+impl OpenSingleFile<'_> {
+    /// Shows the dialog and maps the chosen file into memory.
+    ///
+    /// # Safety
+    ///
+    /// The mapping stays valid after the file is closed, but it isn't a copy: if the file is
+    /// changed or truncated by this or any other process while it's mapped, reading the buffer
+    /// is undefined behavior. Only use it on files nothing else will write to.
+    pub unsafe fn show_mmap(self) -> Result<Option<memmap2::Mmap>> {
+        self.show()?.map(map_file).transpose()
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl OpenMultipleFile<'_> {
+    /// Shows the dialog and maps every chosen file into memory.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`OpenSingleFile::show_mmap`], for each of the files.
+    pub unsafe fn show_mmap(self) -> Result<Vec<memmap2::Mmap>> {
+        self.show()?.into_iter().map(map_file).collect()
+    }
+}
+
+impl OpenMultipleFile<'_> {
+    // None of the backends can enforce a limit while the dialog is open, so it is checked
+    // against whatever the user ended up selecting. Sorting comes first, so that truncating
+    // keeps the same files every time.
+    pub(crate) fn finish_selection(
+        &self,
+        mut paths: Vec<(PathBuf, Option<PathBuf>)>,
+    ) -> Result<Vec<(PathBuf, Option<PathBuf>)>> {
+        match self.sort {
+            SortOrder::Unsorted => {}
+            SortOrder::FileName => paths
+                .sort_by(|(a, _), (b, _)| a.file_name().cmp(&b.file_name()).then_with(|| a.cmp(b))),
+            SortOrder::Path => paths.sort_by(|(a, _), (b, _)| a.cmp(b)),
+        }
+
+        match self.max_selection {
+            Some(max) if paths.len() > max => {
+                if self.truncate_selection {
+                    paths.truncate(max);
+                    Ok(paths)
+                } else {
+                    Err(Error::TooManySelected(paths.len()))
+                }
+            }
+            _ => Ok(paths),
+        }
+    }
+}
+
+impl SaveFile<'_> {
+    pub(crate) fn suggested_name(&self) -> Result<Cow<'_, str>> {
+        if !self.unique_name || self.name.is_empty() {
+            return Ok(Cow::Borrowed(self.name));
+        }
+
+        match resolve_location(self.dir, self.location_url, self.known_folder)? {
+            Some(dir) if !dir.is_empty() => {
+                let dir = PathBuf::from(&*expand_home(&dir));
+                Ok(unique_name(&dir, self.name))
+            }
+            _ => Ok(Cow::Borrowed(self.name)),
+        }
+    }
+
+    pub(crate) fn checked_default_filter(&self) -> Result<usize> {
+        match self.default_filter {
+            0 => Ok(0),
+            index if index < self.filters.len() + self.mime_filters.len() => Ok(index),
+            index => Err(Error::InvalidFilterIndex(index)),
+        }
+    }
+
+    // Not every backend asks before replacing an existing file, so the confirmation is done here
+    // for those that don't. Declining it brings the save dialog back.
+    #[cfg(not(target_os = "macos"))]
+    pub(crate) fn with_overwrite_confirmation(
+        &self,
+        mut show: impl FnMut() -> Result<Option<PathBuf>>,
+    ) -> Result<Option<PathBuf>> {
+        use crate::{MessageConfirm, MessageType};
+
+        loop {
+            let path = match show()? {
+                Some(path) => path,
+                None => return Ok(None),
+            };
+
+            if !self.confirm_overwrite || !path.exists() {
+                return Ok(Some(path));
+            }
+
+            let text = format!(
+                "\"{}\" already exists. Do you want to replace it?",
+                path.file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy(),
+            );
+
+            let dialog = MessageConfirm {
+                title: Some("Confirm Save"),
+                text: &text,
+                typ: MessageType::Warning,
+                timeout: self.timeout,
+                ..Default::default()
+            };
+
+            if dialog.show()? {
+                return Ok(Some(path));
+            }
+        }
+    }
+}
+
+// The number goes before the extension, and a leading dot doesn't start one.
+fn unique_name<'a>(dir: &std::path::Path, name: &'a str) -> Cow<'a, str> {
+    if !dir.join(name).exists() {
+        return Cow::Borrowed(name);
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        Some(i) if i > 0 => name.split_at(i),
+        _ => (name, ""),
+    };
+
+    let mut n = 1;
+    loop {
+        let candidate = format!("{} ({}){}", stem, n, extension);
+        if !dir.join(&candidate).exists() {
+            return Cow::Owned(candidate);
+        }
+        n += 1;
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn initial_save_path(dir: Option<&str>, name: &str) -> PathBuf {
+    match dir {
+        Some(dir) => Path::new(dir).join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+// zenity, kdialog and `choose file name` hand back the name as it was typed, and Windows only
+// ever adds a missing extension, so the rest of `SaveFile::strict_extension` is settled here.
+pub(crate) fn with_save_extension(
+    mut path: PathBuf,
+    filter: Option<&FileFilter>,
+    strict: Option<bool>,
+) -> PathBuf {
+    let first = match filter.and_then(|filter| filter.extensions.first()) {
+        Some(first) => first,
+        None => return path,
+    };
+
+    let listed = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| {
+            filter
+                .into_iter()
+                .flat_map(|filter| filter.extensions.iter())
+                .any(|ext| ext.eq_ignore_ascii_case(extension))
+        });
+
+    let replace = match strict {
+        Some(true) => listed != Some(true),
+        Some(false) => false,
+        None => listed.is_none(),
+    };
+
+    if replace {
+        path.set_extension(first);
+    }
+
+    path
+}
+
+// The unnamed `filter` is kept around as the first group, ahead of the named ones and then the
+// ones made from MIME types. With `merged` given, the merged group from `merge_filters_default`
+// comes before all of them, and its extensions are kept in `merged`.
+pub(crate) fn filter_groups<'a>(
+    filter: Option<&'a [&'a str]>,
+    filters: &[FileFilter<'a>],
+    mime_filters: &'a [(&'a str, Vec<&'a str>)],
+    merged: Option<&'a mut Vec<&'a str>>,
+) -> Vec<FileFilter<'a>> {
+    let unnamed = filter.map(|extensions| FileFilter {
+        name: "",
+        extensions,
+    });
+
+    let mime = mime_filters
+        .iter()
+        .map(|(name, extensions)| FileFilter { name, extensions });
+
+    let mut groups: Vec<FileFilter<'a>> = unnamed
+        .into_iter()
+        .chain(filters.iter().copied())
+        .chain(mime)
+        .collect();
+
+    if let Some(merged) = merged.filter(|_| groups.len() > 1) {
+        for extension in groups.iter().flat_map(|group| group.extensions) {
+            if !merged.contains(extension) {
+                merged.push(extension);
+            }
+        }
+
+        groups.insert(
+            0,
+            FileFilter {
+                name: "All supported files",
+                extensions: merged,
+            },
+        );
+    }
+
+    groups
+}
+
+// Extensions are given without the leading dot or a wildcard, as in "png" or "tar.gz". Backends
+// build patterns and lists out of them, where these characters would break the pattern or split
+// it in two.
+const EXTENSION_RESERVED: &[char] = &['*', '?', ';', '|', '/', '\\'];
+
+fn validate_filter_groups(filters: &[FileFilter]) -> Result<()> {
+    for filter in filters {
+        if filter.extensions.is_empty() {
+            return Err(Error::InvalidFilter(filter.name.to_string()));
+        }
+
+        let malformed = filter.extensions.iter().find(|extension| {
+            extension.is_empty()
+                || extension.starts_with('.')
+                || extension.contains(EXTENSION_RESERVED)
+                || extension.contains(char::is_whitespace)
+        });
+
+        if let Some(extension) = malformed {
+            return Err(Error::InvalidFilter(extension.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+// Checks the filters without showing anything, since backends would otherwise only trip over a
+// malformed one while showing the dialog, if at all. Groups from `add_mime_filter` only ever
+// hold known extensions, and MIME types without any are already left out when they're added.
+impl OpenSingleFile<'_> {
+    pub fn validate_filters(&self) -> Result<()> {
+        validate_filter_groups(&filter_groups(
+            self.filter,
+            &self.filters,
+            &self.mime_filters,
+            None,
+        ))
+    }
+}
+
+impl OpenMultipleFile<'_> {
+    pub fn validate_filters(&self) -> Result<()> {
+        validate_filter_groups(&filter_groups(
+            self.filter,
+            &self.filters,
+            &self.mime_filters,
+            None,
+        ))
+    }
+}
+
+impl OpenSingleItem<'_> {
+    pub fn validate_filters(&self) -> Result<()> {
+        validate_filter_groups(&filter_groups(
+            self.filter,
+            &self.filters,
+            &self.mime_filters,
+            None,
+        ))
+    }
+}
+
+impl SaveFile<'_> {
+    /// Also checks that `default_filter` is one of the filters
+    pub fn validate_filters(&self) -> Result<()> {
+        validate_filter_groups(&filter_groups(
+            None,
+            &self.filters,
+            &self.mime_filters,
+            None,
+        ))?;
+        self.checked_default_filter().map(|_| ())
+    }
+}
+
+const MIME_EXTENSIONS: &[(&str, &[&str])] = &[
+    ("application/gzip", &["gz", "tgz"]),
+    ("application/json", &["json"]),
+    ("application/pdf", &["pdf"]),
+    ("application/x-7z-compressed", &["7z"]),
+    ("application/x-bzip2", &["bz2"]),
+    ("application/x-tar", &["tar"]),
+    ("application/x-xz", &["xz"]),
+    ("application/xml", &["xml"]),
+    ("application/zip", &["zip"]),
+    ("audio/mpeg", &["mp3"]),
+    ("audio/ogg", &["ogg", "oga"]),
+    ("audio/wav", &["wav"]),
+    ("image/bmp", &["bmp"]),
+    ("image/gif", &["gif"]),
+    ("image/jpeg", &["jpg", "jpeg"]),
+    ("image/png", &["png"]),
+    ("image/svg+xml", &["svg"]),
+    ("image/webp", &["webp"]),
+    ("text/csv", &["csv"]),
+    ("text/html", &["html", "htm"]),
+    ("text/markdown", &["md", "markdown"]),
+    ("text/plain", &["txt"]),
+    ("text/xml", &["xml"]),
+    ("video/mp4", &["mp4"]),
+    ("video/webm", &["webm"]),
+];
+
+// The subtypes of "application" in `MIME_EXTENSIONS` that `FileKind` counts as archives
+const ARCHIVE_TYPES: &[&str] = &["gzip", "x-7z-compressed", "x-bzip2", "x-tar", "x-xz", "zip"];
+
+fn mime_extensions(mime_types: &[&str]) -> Vec<&'static str> {
+    let mut extensions = vec![];
+
+    for mime_type in mime_types {
+        let matches =
+            MIME_EXTENSIONS
+                .iter()
+                .filter(|(known, _)| match mime_type.strip_suffix("/*") {
+                    Some(top) => known.split('/').next() == Some(top),
+                    None => known.eq_ignore_ascii_case(mime_type),
+                });
+
+        for extension in matches.flat_map(|(_, extensions)| extensions.iter()) {
+            if !extensions.contains(extension) {
+                extensions.push(*extension);
+            }
+        }
+    }
+
+    extensions
+}
+
+fn push_mime_filter<'a>(
+    mime_filters: &mut Vec<(&'a str, Vec<&'a str>)>,
+    name: &'a str,
+    mime_types: &[&str],
+) {
+    let extensions = mime_extensions(mime_types);
+    if !extensions.is_empty() {
+        mime_filters.push((name, extensions));
+    }
+}
+
+// Filters picked under a `remember_filter` key, kept for the lifetime of the process.
+#[cfg(not(target_os = "macos"))]
+static REMEMBERED_FILTERS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn remembered_filter(key: Option<&str>, filters: &[FileFilter]) -> usize {
+    let remembered = key.and_then(|key| {
+        let remembered = REMEMBERED_FILTERS.lock().unwrap_or_else(|e| e.into_inner());
+        remembered.get(key).copied()
+    });
+
+    match remembered {
+        Some(index) if index < filters.len() => index,
+        _ => 0,
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn remember_filter(key: Option<&str>, index: Option<usize>) {
+    if let (Some(key), Some(index)) = (key, index) {
+        let mut remembered = REMEMBERED_FILTERS.lock().unwrap_or_else(|e| e.into_inner());
+        remembered.insert(key.to_string(), index);
+    }
+}
+
+// For backends that don't report which filter was used, it's inferred from the chosen file,
+// preferring the filter that was selected when the dialog opened.
+pub(crate) fn matching_filter(
+    filters: &[FileFilter],
+    default: usize,
+    path: &Path,
+    case_sensitive: bool,
+) -> Option<usize> {
+    let extension = path.extension()?.to_str()?;
+    let matches = |filter: &FileFilter| {
+        filter.extensions.iter().any(|ext| match case_sensitive {
+            true => *ext == extension,
+            false => ext.eq_ignore_ascii_case(extension),
+        })
+    };
+
+    match filters.get(default) {
+        Some(filter) if matches(filter) => Some(default),
+        _ => filters.iter().position(matches),
+    }
+}
+
+// The starting location may be given as a URL instead of a path. It takes precedence over `dir`
+// when both are set, and only `file:` URLs can be turned into paths.
+// Backends fall back to some other folder when the starting one doesn't exist, so it's created
+// beforehand when asked to.
+pub(crate) fn create_location(
+    dir: Option<&str>,
+    url: Option<&str>,
+    known: Option<KnownFolder>,
+    create: bool,
+) -> Result<()> {
+    // The URL is checked whether or not anything is created, since macOS would otherwise take any
+    // scheme and open somewhere unrelated
+    if let Some(url) = url {
+        file_url_to_path(url)?;
+    }
+    if !create {
+        return Ok(());
+    }
+
+    match resolve_location(dir, url, known)? {
+        Some(dir) if !dir.is_empty() => Ok(std::fs::create_dir_all(&*expand_home(&dir))?),
+        _ => Ok(()),
+    }
+}
+
+// The macOS backend expands a leading `~` itself, so the folder it'll open has to be worked out
+// the same way.
+fn expand_home(dir: &str) -> Cow<'_, str> {
+    match dir.strip_prefix('~') {
+        Some(rest) if cfg!(target_os = "macos") => match std::env::var("HOME") {
+            Ok(home) => Cow::Owned(format!("{}{}", home, rest)),
+            Err(_) => Cow::Borrowed(dir),
+        },
+        _ => Cow::Borrowed(dir),
+    }
+}
+
+pub(crate) fn resolve_location<'a>(
+    dir: Option<&'a str>,
+    url: Option<&'a str>,
+    known: Option<KnownFolder>,
+) -> Result<Option<Cow<'a, str>>> {
+    match url {
+        Some(url) => file_url_to_path(url).map(|path| Some(Cow::Owned(path))),
+        None => Ok(start_dir(dir, known)),
+    }
+}
+
+// The known folder is only looked up when there's no `dir`
+pub(crate) fn start_dir<'a>(
+    dir: Option<&'a str>,
+    known: Option<KnownFolder>,
+) -> Option<Cow<'a, str>> {
+    match dir {
+        Some(dir) => Some(Cow::Borrowed(dir)),
+        None => known
+            .and_then(crate::r#impl::known_folder_path)
+            .map(Cow::Owned),
+    }
+}
+
+fn file_url_to_path(url: &str) -> Result<String> {
+    let rest = match url.find(':') {
+        Some(i) if url[..i].eq_ignore_ascii_case("file") => &url[i + 1..],
+        _ => return Err(Error::UnsupportedUrl(url.to_string())),
+    };
+
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+
+    let (host, path) = match rest.strip_prefix("//") {
+        Some(authority) => match authority.find('/') {
+            Some(i) => (&authority[..i], &authority[i..]),
+            None => (authority, "/"),
+        },
+        None => ("", rest),
+    };
+
+    let path = String::from_utf8(percent_decode(path))?;
+
+    if cfg!(target_os = "windows") {
+        let path = path.replace('/', "\\");
+        match host {
+            "" | "localhost" => {
+                let bytes = path.as_bytes();
+                if bytes.len() >= 3 && bytes[0] == b'\\' && bytes[2] == b':' {
+                    Ok(path[1..].to_string())
+                } else {
+                    Ok(path)
+                }
+            }
+            host => Ok(format!("\\\\{}{}", host, path)),
+        }
+    } else {
+        match host {
+            "" | "localhost" => Ok(path),
+            _ => Err(Error::UnsupportedUrl(url.to_string())),
+        }
+    }
+}
+
+fn percent_decode(s: &str) -> Vec<u8> {
+    fn hex(byte: u8) -> Option<u8> {
+        (byte as char).to_digit(16).map(|digit| digit as u8)
+    }
+
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    decoded
 }
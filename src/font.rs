@@ -0,0 +1,20 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Only Windows has a font picker that hands back a choice. zenity, kdialog and osascript don't,
+/// so there the dialog fails with `Error::Unsupported`.
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct PickFont {
+    pub default: Option<FontSelection>,
+}
+
+/// `size` is in points.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FontSelection {
+    pub family: String,
+    pub size: f32,
+    pub bold: bool,
+    pub italic: bool,
+}
@@ -0,0 +1,31 @@
+use std::sync::RwLock;
+
+/// What the global hook gets to see of a dialog that's about to be shown. Only message and input
+/// dialogs have a title.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DialogInfo<'a> {
+    pub dialog: &'static str,
+    pub title: Option<&'a str>,
+}
+
+pub type GlobalHook = Box<dyn Fn(&DialogInfo) + Send + Sync>;
+
+static GLOBAL_HOOK: RwLock<Option<GlobalHook>> = RwLock::new(None);
+
+/// Calls `hook` on the showing thread before every dialog, including mocked ones. Setting a hook
+/// replaces the previous one.
+pub fn set_global_hook(hook: GlobalHook) {
+    *GLOBAL_HOOK.write().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+}
+
+pub fn clear_global_hook() {
+    *GLOBAL_HOOK.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub(crate) fn before_show(dialog: &'static str, title: Option<&str>) {
+    let hook = GLOBAL_HOOK.read().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(hook) = &*hook {
+        hook(&DialogInfo { dialog, title });
+    }
+}
@@ -1,101 +1,398 @@
-use super::{should_use, Error, UseCommand};
+use super::{
+    argv, failure, output_native, should_use, trim_newline, with_retry, with_status, Error,
+    UseCommand,
+};
 use crate::{
-    r#impl::OpenDialogTarget, Dialog, OpenMultipleFile, OpenSingleDir, OpenSingleFile, Result,
+    file::{
+        create_location, filter_groups, initial_save_path, item_kind, matching_filter,
+        remember_filter, remembered_filter, resolve_location, resolve_shortcut,
+        with_save_extension,
+    },
+    r#impl::{self, OpenDialogTarget},
+    trace, Backend, Dialog, FileFilter, ItemKind, KnownFolder, NativeDialogHandle,
+    OpenMultipleFile, OpenSingleDir, OpenSingleFile, OpenSingleItem, Result, SaveFile,
 };
+use std::borrow::Cow;
+use std::env;
 use std::ffi::OsStr;
+use std::fs;
 use std::os::unix::ffi::OsStrExt;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
 
 impl Dialog for OpenSingleFile<'_> {
     type Output = Option<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        match should_use() {
-            Some(UseCommand::KDialog(command)) => {
-                dialog_implementation_kdialog(ImplementationParams {
-                    command,
-                    dir: self.dir,
-                    filter: self.filter,
-                    multiple: false,
-                    target: OpenDialogTarget::File,
-                })
-            }
-            Some(UseCommand::Zenity(command)) => {
-                dialog_implementation_zenity(ImplementationParams {
-                    command,
-                    dir: self.dir,
-                    filter: self.filter,
-                    multiple: false,
-                    target: OpenDialogTarget::File,
-                })
+    fn show_ref(&self) -> Result<Self::Output> {
+        Ok(self.show_with_links()?.map(|(path, _)| path))
+    }
+}
+
+impl OpenSingleFile<'_> {
+    pub(crate) fn show_with_links(&self) -> Result<Option<(PathBuf, Option<PathBuf>)>> {
+        trace::dialog("OpenSingleFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            if let Some(path) = self.single_match() {
+                return Ok(Some((path, None)));
             }
-            None => Err(Error::NoImplementation),
-        }
-        .map(|ok| ok.map(|some| bytes_to_path_buf(&some)))
+
+            self.with_params(|params| {
+                let path = r#impl::backend("OpenSingleFile", || {
+                    Ok(open_dialog(params)?.map(|some| bytes_to_path_buf(&some)))
+                })?
+                .map(|path| resolve_shortcut(path, self.resolve_shortcuts));
+
+                if let Some((path, _)) = &path {
+                    let index = matching_filter(
+                        params.filters,
+                        params.default_filter,
+                        path,
+                        params.case_sensitive,
+                    );
+                    remember_filter(self.remember_filter, index);
+                }
+
+                Ok(path)
+            })
+        })
+    }
+}
+
+impl OpenSingleFile<'_> {
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        self.with_params(|params| Ok(open_dialog_command(params)))
+            .ok()
+            .flatten()
+    }
+
+    fn with_params<T>(&self, f: impl FnOnce(&ImplementationParams) -> Result<T>) -> Result<T> {
+        let dir = absolute_dir(resolve_location(
+            self.dir,
+            self.location_url,
+            self.known_folder,
+        )?)?;
+        let mut merged = vec![];
+        let filters = filter_groups(
+            self.filter,
+            &self.filters,
+            &self.mime_filters,
+            self.merge_filters_default.then_some(&mut merged),
+        );
+
+        f(&ImplementationParams {
+            dir: dir.as_deref(),
+            filters: &filters,
+            default_filter: remembered_filter(self.remember_filter, &filters),
+            multiple: false,
+            target: OpenDialogTarget::File,
+            retry: self.retry,
+            timeout: self.timeout,
+            cwd: self.child_cwd,
+            native: self.configure_native,
+            case_sensitive: self.case_sensitive_filter,
+        })
     }
 }
 
 impl Dialog for OpenMultipleFile<'_> {
     type Output = Vec<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        let lf_separated = match should_use() {
-            Some(UseCommand::KDialog(command)) => {
-                dialog_implementation_kdialog(ImplementationParams {
-                    command,
-                    dir: self.dir,
-                    filter: self.filter,
-                    multiple: true,
-                    target: OpenDialogTarget::File,
-                })
-            }
-            Some(UseCommand::Zenity(command)) => {
-                dialog_implementation_zenity(ImplementationParams {
-                    command,
-                    dir: self.dir,
-                    filter: self.filter,
-                    multiple: true,
-                    target: OpenDialogTarget::File,
-                })
-            }
-            None => Err(Error::NoImplementation),
-        };
+    fn show_ref(&self) -> Result<Self::Output> {
+        let paths = self.show_with_links()?;
+        Ok(paths.into_iter().map(|(path, _)| path).collect())
+    }
+}
 
-        match lf_separated {
-            Ok(Some(s)) => Ok(s.split(|c| *c == b'\n').map(bytes_to_path_buf).collect()),
-            Ok(_) => Ok(vec![]),
-            Err(e) => Err(e),
-        }
+impl OpenMultipleFile<'_> {
+    pub(crate) fn show_with_links(&self) -> Result<Vec<(PathBuf, Option<PathBuf>)>> {
+        trace::dialog("OpenMultipleFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+
+            let paths = self.with_params(|params| {
+                let paths: Vec<_> = r#impl::backend("OpenMultipleFile", || {
+                    Ok(match open_dialog(params)? {
+                        Some(s) => s.split(|c| *c == b'\n').map(bytes_to_path_buf).collect(),
+                        None => vec![],
+                    })
+                })?
+                .into_iter()
+                .map(|path| resolve_shortcut(path, self.resolve_shortcuts))
+                .collect();
+
+                if let Some((path, _)) = paths.first() {
+                    let index = matching_filter(
+                        params.filters,
+                        params.default_filter,
+                        path,
+                        params.case_sensitive,
+                    );
+                    remember_filter(self.remember_filter, index);
+                }
+
+                Ok(paths)
+            })?;
+
+            self.finish_selection(paths)
+        })
+    }
+}
+
+impl OpenMultipleFile<'_> {
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        self.with_params(|params| Ok(open_dialog_command(params)))
+            .ok()
+            .flatten()
+    }
+
+    fn with_params<T>(&self, f: impl FnOnce(&ImplementationParams) -> Result<T>) -> Result<T> {
+        let dir = absolute_dir(resolve_location(
+            self.dir,
+            self.location_url,
+            self.known_folder,
+        )?)?;
+        let mut merged = vec![];
+        let filters = filter_groups(
+            self.filter,
+            &self.filters,
+            &self.mime_filters,
+            self.merge_filters_default.then_some(&mut merged),
+        );
+
+        f(&ImplementationParams {
+            dir: dir.as_deref(),
+            filters: &filters,
+            default_filter: remembered_filter(self.remember_filter, &filters),
+            multiple: true,
+            target: OpenDialogTarget::File,
+            retry: self.retry,
+            timeout: self.timeout,
+            cwd: self.child_cwd,
+            native: self.configure_native,
+            case_sensitive: self.case_sensitive_filter,
+        })
     }
 }
 
 impl Dialog for OpenSingleDir<'_> {
     type Output = Option<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        match should_use() {
-            Some(UseCommand::KDialog(command)) => {
-                dialog_implementation_kdialog(ImplementationParams {
-                    command,
-                    dir: self.dir,
-                    filter: None,
-                    multiple: false,
-                    target: OpenDialogTarget::Directory,
-                })
-            }
-            Some(UseCommand::Zenity(command)) => {
-                dialog_implementation_zenity(ImplementationParams {
-                    command,
-                    dir: self.dir,
-                    filter: None,
-                    multiple: false,
-                    target: OpenDialogTarget::Directory,
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("OpenSingleDir", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+
+            self.with_params(|params| {
+                r#impl::backend("OpenSingleDir", || {
+                    Ok(open_dialog(params)?.map(|some| bytes_to_path_buf(&some)))
                 })
+            })
+        })
+    }
+}
+
+impl OpenSingleDir<'_> {
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        self.with_params(|params| Ok(open_dialog_command(params)))
+            .ok()
+            .flatten()
+    }
+
+    fn with_params<T>(&self, f: impl FnOnce(&ImplementationParams) -> Result<T>) -> Result<T> {
+        let dir = absolute_dir(resolve_location(
+            self.dir,
+            self.location_url,
+            self.known_folder,
+        )?)?;
+
+        f(&ImplementationParams {
+            dir: dir.as_deref(),
+            filters: &[],
+            default_filter: 0,
+            multiple: false,
+            target: OpenDialogTarget::Directory,
+            retry: self.retry,
+            timeout: self.timeout,
+            cwd: self.child_cwd,
+            native: self.configure_native,
+            case_sensitive: false,
+        })
+    }
+}
+
+impl Dialog for OpenSingleItem<'_> {
+    type Output = Option<(PathBuf, ItemKind)>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("OpenSingleItem", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+
+            self.with_params(|params| {
+                let path = r#impl::backend("OpenSingleItem", || {
+                    Ok(open_dialog(params)?.map(|some| bytes_to_path_buf(&some)))
+                })?;
+
+                Ok(path.map(|path| item_kind(resolve_shortcut(path, self.resolve_shortcuts).0)))
+            })
+        })
+    }
+}
+
+impl OpenSingleItem<'_> {
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        self.with_params(|params| Ok(open_dialog_command(params)))
+            .ok()
+            .flatten()
+    }
+
+    fn with_params<T>(&self, f: impl FnOnce(&ImplementationParams) -> Result<T>) -> Result<T> {
+        let dir = absolute_dir(resolve_location(
+            self.dir,
+            self.location_url,
+            self.known_folder,
+        )?)?;
+        let mut merged = vec![];
+        let filters = filter_groups(
+            self.filter,
+            &self.filters,
+            &self.mime_filters,
+            self.merge_filters_default.then_some(&mut merged),
+        );
+
+        f(&ImplementationParams {
+            dir: dir.as_deref(),
+            filters: &filters,
+            default_filter: 0,
+            multiple: false,
+            target: OpenDialogTarget::FileOrDirectory,
+            retry: self.retry,
+            timeout: self.timeout,
+            cwd: self.child_cwd,
+            native: self.configure_native,
+            case_sensitive: self.case_sensitive_filter,
+        })
+    }
+}
+
+impl Dialog for SaveFile<'_> {
+    type Output = Option<PathBuf>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("SaveFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            let params = self.params()?;
+
+            let show = || {
+                let path = r#impl::backend("SaveFile", || {
+                    with_retry(self.retry, || match should_use() {
+                        Some(UseCommand::KDialog(command)) => {
+                            let mut command = save_command_kdialog(command, &params);
+                            save_implementation_kdialog(&mut command, self.timeout, params.native)
+                        }
+                        Some(UseCommand::Zenity(command)) => {
+                            let mut command = save_command_zenity(command, &params);
+                            save_implementation_zenity(&mut command, self.timeout, params.native)
+                        }
+                        None => Err(Error::NoImplementation),
+                    })
+                })?;
+
+                Ok(params.with_extension(path))
+            };
+
+            // KDE's save dialog already asks before replacing a file, so asking again would
+            // only repeat the question
+            match should_use() {
+                Some(UseCommand::KDialog(_)) => show(),
+                _ => self.with_overwrite_confirmation(show),
             }
-            None => Err(Error::NoImplementation),
+        })
+    }
+}
+
+impl SaveFile<'_> {
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        let params = self.params().ok()?;
+
+        match should_use()? {
+            UseCommand::KDialog(command) => Some(argv(&save_command_kdialog(command, &params))),
+            UseCommand::Zenity(command) => Some(argv(&save_command_zenity(command, &params))),
         }
-        .map(|ok| ok.map(|some| bytes_to_path_buf(&some)))
+    }
+
+    fn params(&self) -> Result<SaveParams<'_>> {
+        let dir = resolve_location(self.dir, self.location_url, self.known_folder)?;
+
+        Ok(SaveParams {
+            path: absolute(initial_save_path(dir.as_deref(), &self.suggested_name()?))?,
+            filters: filter_groups(None, &self.filters, &self.mime_filters, None),
+            default_filter: self.checked_default_filter()?,
+            strict_extension: self.strict_extension,
+            cwd: self.child_cwd,
+            native: self.configure_native,
+            case_sensitive: self.case_sensitive_filter,
+        })
+    }
+}
+
+struct SaveParams<'a> {
+    path: PathBuf,
+    filters: Vec<FileFilter<'a>>,
+    default_filter: usize,
+    strict_extension: Option<bool>,
+    cwd: Option<&'a Path>,
+    native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
+    case_sensitive: bool,
+}
+
+impl SaveParams<'_> {
+    fn with_extension(&self, path: Option<PathBuf>) -> Option<PathBuf> {
+        let filter = self.filters.get(self.default_filter);
+        path.map(|path| with_save_extension(path, filter, self.strict_extension))
     }
 }
 
@@ -103,19 +400,98 @@ fn bytes_to_path_buf(buf: &[u8]) -> PathBuf {
     PathBuf::from(OsStr::from_bytes(buf))
 }
 
+// Both tools take glob patterns, which GTK and Qt match case-sensitively on Linux. Each letter
+// becomes a class of both of its cases unless the case has to match, so "png" is "*.[pP][nN][gG]".
+fn patterns(filter: &FileFilter, case_sensitive: bool) -> String {
+    let patterns: Vec<String> = filter
+        .extensions
+        .iter()
+        .map(|extension| match case_sensitive {
+            true => format!("*.{}", extension),
+            false => format!("*.{}", any_case(extension)),
+        })
+        .collect();
+    patterns.join(" ")
+}
+
+fn any_case(extension: &str) -> String {
+    extension
+        .chars()
+        .map(|c| match c.is_ascii_alphabetic() {
+            true => format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase()),
+            false => c.to_string(),
+        })
+        .collect()
+}
+
+// Both zenity and kdialog start out on the first filter they're given.
+fn default_first<'a, 'b>(
+    filters: &'a [FileFilter<'b>],
+    default: usize,
+) -> impl Iterator<Item = &'a FileFilter<'b>> {
+    let rest = filters
+        .iter()
+        .enumerate()
+        .filter(move |(i, _)| *i != default)
+        .map(|(_, filter)| filter);
+    filters.get(default).into_iter().chain(rest)
+}
+
 struct ImplementationParams<'a> {
-    command: Command,
-    dir: Option<&'a str>,
-    filter: Option<&'a [&'a str]>,
+    dir: Option<&'a Path>,
+    filters: &'a [FileFilter<'a>],
+    default_filter: usize,
     multiple: bool,
     target: OpenDialogTarget,
+    retry: Option<(u32, Duration)>,
+    timeout: Option<Duration>,
+    cwd: Option<&'a Path>,
+    native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
+    case_sensitive: bool,
 }
 
-fn dialog_implementation_kdialog(mut params: ImplementationParams) -> Result<Option<Vec<u8>>> {
-    let command = &mut params.command;
+// zenity and kdialog take a relative path from their own working directory, which is no longer
+// the process's once `child_cwd` is set. The process's may also have moved on since the dialog
+// was built, so relative paths are settled against it when the dialog is shown.
+fn absolute(path: PathBuf) -> Result<PathBuf> {
+    match path.is_relative() && !path.as_os_str().is_empty() {
+        true => Ok(env::current_dir()?.join(path)),
+        false => Ok(path),
+    }
+}
 
+fn absolute_dir(dir: Option<Cow<str>>) -> Result<Option<PathBuf>> {
+    dir.map(|dir| absolute(PathBuf::from(dir.into_owned())))
+        .transpose()
+}
+
+fn open_dialog(params: &ImplementationParams) -> Result<Option<Vec<u8>>> {
+    with_retry(params.retry, || match should_use() {
+        Some(UseCommand::KDialog(command)) => {
+            let mut command = command_kdialog(command, params);
+            dialog_implementation_kdialog(&mut command, params.timeout, params.native)
+        }
+        Some(UseCommand::Zenity(command)) => {
+            let mut command = command_zenity(command, params);
+            dialog_implementation_zenity(&mut command, params.timeout, params.native)
+        }
+        None => Err(Error::NoImplementation),
+    })
+}
+
+fn open_dialog_command(params: &ImplementationParams) -> Option<Vec<String>> {
+    match should_use()? {
+        UseCommand::KDialog(command) => Some(argv(&command_kdialog(command, params))),
+        UseCommand::Zenity(command) => Some(argv(&command_zenity(command, params))),
+    }
+}
+
+fn command_kdialog(mut command: Command, params: &ImplementationParams) -> Command {
     match params.target {
-        OpenDialogTarget::File => command.arg("--getopenfilename"),
+        // Neither tool can pick both, so it's a file picker
+        OpenDialogTarget::File | OpenDialogTarget::FileOrDirectory => {
+            command.arg("--getopenfilename")
+        }
         OpenDialogTarget::Directory => command.arg("--getexistingdirectory"),
     };
 
@@ -125,26 +501,24 @@ fn dialog_implementation_kdialog(mut params: ImplementationParams) -> Result<Opt
     };
 
     if params.multiple {
-        command.args(&["--multiple", "--separate-output"]);
-    }
-
-    if let Some(filter) = params.filter {
-        let types: Vec<String> = filter.iter().map(|s| format!("*.{}", s)).collect();
-        command.arg(types.join(" "));
+        command.args(["--multiple", "--separate-output"]);
     }
 
-    let output = command.output()?;
+    kdialog_filters(
+        &mut command,
+        params.filters,
+        params.default_filter,
+        params.case_sensitive,
+    );
 
-    match output.status.code() {
-        Some(0) => Ok(Some(output.stdout)),
-        Some(1) => Ok(None),
-        _ => Err(Error::UnexpectedOutput("kdialog")),
+    if let Some(cwd) = params.cwd {
+        command.current_dir(cwd);
     }
-}
 
-fn dialog_implementation_zenity(mut params: ImplementationParams) -> Result<Option<Vec<u8>>> {
-    let command = &mut params.command;
+    command
+}
 
+fn command_zenity(mut command: Command, params: &ImplementationParams) -> Command {
     command.arg("--file-selection");
 
     if params.target == OpenDialogTarget::Directory {
@@ -152,7 +526,7 @@ fn dialog_implementation_zenity(mut params: ImplementationParams) -> Result<Opti
     }
 
     if params.multiple {
-        command.args(&["--multiple", "--separator", "\n"]);
+        command.args(["--multiple", "--separator", "\n"]);
     }
 
     command.arg("--filename");
@@ -162,17 +536,205 @@ fn dialog_implementation_zenity(mut params: ImplementationParams) -> Result<Opti
         None => command.arg(""),
     };
 
-    if let Some(filter) = params.filter {
-        let types: Vec<String> = filter.iter().map(|s| format!("*.{}", s)).collect();
+    zenity_filters(
+        &mut command,
+        params.filters,
+        params.default_filter,
+        params.case_sensitive,
+    );
+
+    if let Some(cwd) = params.cwd {
+        command.current_dir(cwd);
+    }
+
+    command
+}
+
+fn dialog_implementation_kdialog(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    native: Option<&(dyn Fn(NativeDialogHandle) + Sync)>,
+) -> Result<Option<Vec<u8>>> {
+    let output = output_native(command, Backend::KDialog, timeout, native)?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(trim_newline(&output.stdout).to_vec())),
+        Some(1) => Ok(None),
+        _ => Err(failure("kdialog", &output.stderr)),
+    }
+}
+
+fn dialog_implementation_zenity(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    native: Option<&(dyn Fn(NativeDialogHandle) + Sync)>,
+) -> Result<Option<Vec<u8>>> {
+    let output = output_native(command, Backend::Zenity, timeout, native)?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(trim_newline(&output.stdout).to_vec())),
+        Some(1) => Ok(None),
+        _ => Err(failure("zenity", &output.stderr)),
+    }
+}
+
+// A filter without a name is labelled with its patterns, which are only worth reading as given
+fn kdialog_filters(
+    command: &mut Command,
+    filters: &[FileFilter],
+    default: usize,
+    case_sensitive: bool,
+) {
+    if !filters.is_empty() {
+        let groups: Vec<String> = default_first(filters, default)
+            .map(|filter| match (filter.name, case_sensitive) {
+                ("", true) => patterns(filter, true),
+                ("", false) => format!("{}|{}", patterns(filter, false), patterns(filter, true)),
+                (name, _) => format!("{}|{}", patterns(filter, case_sensitive), name),
+            })
+            .collect();
+        command.arg(groups.join("\n"));
+    }
+}
+
+fn zenity_filters(
+    command: &mut Command,
+    filters: &[FileFilter],
+    default: usize,
+    case_sensitive: bool,
+) {
+    for filter in default_first(filters, default) {
         command.arg("--file-filter");
-        command.arg(types.join(" "));
+        match (filter.name, case_sensitive) {
+            ("", true) => command.arg(patterns(filter, true)),
+            ("", false) => command.arg(format!(
+                "{} | {}",
+                patterns(filter, true),
+                patterns(filter, false)
+            )),
+            (name, _) => command.arg(format!("{} | {}", name, patterns(filter, case_sensitive))),
+        };
+    }
+}
+
+fn save_command_kdialog(mut command: Command, params: &SaveParams) -> Command {
+    command.arg("--getsavefilename");
+    command.arg(&params.path);
+
+    kdialog_filters(
+        &mut command,
+        &params.filters,
+        params.default_filter,
+        params.case_sensitive,
+    );
+
+    if let Some(cwd) = params.cwd {
+        command.current_dir(cwd);
+    }
+
+    command
+}
+
+fn save_command_zenity(mut command: Command, params: &SaveParams) -> Command {
+    command.args(["--file-selection", "--save"]);
+
+    command.arg("--filename");
+    command.arg(&params.path);
+
+    zenity_filters(
+        &mut command,
+        &params.filters,
+        params.default_filter,
+        params.case_sensitive,
+    );
+
+    if let Some(cwd) = params.cwd {
+        command.current_dir(cwd);
+    }
+
+    command
+}
+
+fn save_implementation_kdialog(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    native: Option<&(dyn Fn(NativeDialogHandle) + Sync)>,
+) -> Result<Option<PathBuf>> {
+    let output = output_native(command, Backend::KDialog, timeout, native)?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(bytes_to_path_buf(trim_newline(&output.stdout)))),
+        Some(1) => Ok(None),
+        _ => Err(failure("kdialog", &output.stderr)),
     }
+}
 
-    let output = command.output()?;
+fn save_implementation_zenity(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    native: Option<&(dyn Fn(NativeDialogHandle) + Sync)>,
+) -> Result<Option<PathBuf>> {
+    let output = output_native(command, Backend::Zenity, timeout, native)?;
 
     match output.status.code() {
-        Some(0) => Ok(Some(output.stdout)),
+        Some(0) => Ok(Some(bytes_to_path_buf(trim_newline(&output.stdout)))),
         Some(1) => Ok(None),
-        _ => Err(Error::UnexpectedOutput("zenity")),
+        _ => Err(failure("zenity", &output.stderr)),
+    }
+}
+
+// Reads the folders from user-dirs.dirs, the way xdg-user-dir does, and falls back to the
+// usual names in the home folder for the ones it doesn't list.
+pub(crate) fn known_folder_path(folder: KnownFolder) -> Option<String> {
+    let home = env::var("HOME").ok()?;
+
+    let (key, default) = match folder {
+        KnownFolder::Home => return Some(home),
+        KnownFolder::Desktop => ("XDG_DESKTOP_DIR", "Desktop"),
+        KnownFolder::Documents => ("XDG_DOCUMENTS_DIR", "Documents"),
+        KnownFolder::Downloads => ("XDG_DOWNLOAD_DIR", "Downloads"),
+        KnownFolder::Music => ("XDG_MUSIC_DIR", "Music"),
+        KnownFolder::Pictures => ("XDG_PICTURES_DIR", "Pictures"),
+        KnownFolder::Videos => ("XDG_VIDEOS_DIR", "Videos"),
+    };
+
+    let config = match env::var("XDG_CONFIG_HOME") {
+        Ok(config) if !config.is_empty() => PathBuf::from(config),
+        _ => Path::new(&home).join(".config"),
+    };
+
+    let dirs = fs::read_to_string(config.join("user-dirs.dirs")).unwrap_or_default();
+    let configured = dirs.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+        Some(value.trim_matches('"').replace("$HOME", &home))
+    });
+
+    Some(configured.unwrap_or_else(|| format!("{}/{}", home, default)))
+}
+
+// Symbolic links are the closest thing to shortcuts here.
+pub(crate) fn shortcut_target(path: &Path) -> Option<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gtk_warnings_dont_fail_a_selection() {
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "echo 'Gtk-WARNING **: GtkDialog mapped without a transient parent' >&2; \
+             echo 'dbind-WARNING **: AT-SPI: Error retrieving accessibility bus address' >&2; \
+             echo /tmp/report.txt",
+        ]);
+
+        let selection = dialog_implementation_zenity(&mut command, None, None).unwrap();
+        assert_eq!(selection, Some(b"/tmp/report.txt".to_vec()));
     }
 }
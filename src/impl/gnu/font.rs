@@ -0,0 +1,24 @@
+use super::{should_use, UseCommand};
+use crate::{r#impl, trace, Backend, Dialog, Error, FontSelection, PickFont, Result};
+
+// Neither zenity nor kdialog has a font picker
+impl Dialog for PickFont {
+    type Output = Option<FontSelection>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("PickFont", None, || {
+            r#impl::backend("PickFont", || {
+                let backend = match should_use() {
+                    Some(UseCommand::KDialog(_)) => Backend::KDialog,
+                    Some(UseCommand::Zenity(_)) => Backend::Zenity,
+                    None => return Err(Error::NoImplementation),
+                };
+
+                Err(Error::Unsupported {
+                    dialog: "PickFont",
+                    backend,
+                })
+            })
+        })
+    }
+}
@@ -0,0 +1,219 @@
+use super::{
+    argv, failure, label_arg, output, should_use, trim_newline, with_retry, with_status, UseCommand,
+};
+use crate::{r#impl, trace, Backend, Dialog, Error, Form, InputText, LabelSet, Result};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+impl Dialog for InputText<'_> {
+    type Output = Option<String>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("InputText", Some(self.title), || {
+            r#impl::backend("InputText", || {
+                let output = with_retry(self.retry, || match should_use() {
+                    Some(UseCommand::KDialog(command)) => {
+                        dialog_implementation_kdialog(self.params(command))
+                    }
+                    Some(UseCommand::Zenity(command)) => {
+                        dialog_implementation_zenity(self.params(command))
+                    }
+                    None => Err(Error::NoImplementation),
+                })?;
+
+                match output {
+                    Some(buf) => Ok(Some(String::from_utf8(trim_newline(&buf).to_vec())?)),
+                    None => Ok(None),
+                }
+            })
+        })
+    }
+}
+
+impl InputText<'_> {
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        match should_use()? {
+            UseCommand::KDialog(command) => Some(argv(&command_kdialog(self.params(command)))),
+            UseCommand::Zenity(command) => Some(argv(&command_zenity(self.params(command)))),
+        }
+    }
+
+    fn params(&self, command: Command) -> ImplementationParams<'_> {
+        ImplementationParams {
+            command,
+            title: self.title,
+            text: self.text,
+            default: self.default,
+            labels: self.labels,
+            timeout: self.timeout,
+        }
+    }
+}
+
+// kdialog has no form, so it asks for one field after another, and cancelling any of them cancels
+// the form. zenity's forms can't start out filled in, so zenity does the same whenever a field has
+// a default.
+impl Dialog for Form<'_> {
+    type Output = Option<Vec<String>>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("Form", Some(self.title), || {
+            // There's nothing to ask, and zenity can't show a form without fields
+            if self.fields.is_empty() {
+                return Ok(Some(vec![]));
+            }
+
+            r#impl::backend("Form", || match should_use() {
+                Some(UseCommand::KDialog(command)) => {
+                    form_by_field(command, self, dialog_implementation_kdialog)
+                }
+                Some(UseCommand::Zenity(command)) => {
+                    match self.fields.iter().any(|(_, default)| !default.is_empty()) {
+                        true => form_by_field(command, self, dialog_implementation_zenity),
+                        false => form_zenity(command, self),
+                    }
+                }
+                None => Err(Error::NoImplementation),
+            })
+        })
+    }
+}
+
+fn form_by_field(
+    command: Command,
+    form: &Form,
+    ask: fn(ImplementationParams) -> Result<Option<Vec<u8>>>,
+) -> Result<Option<Vec<String>>> {
+    let program = command.get_program().to_owned();
+    let mut values = vec![];
+
+    for (label, default) in &form.fields {
+        let output = ask(ImplementationParams {
+            command: Command::new(&program),
+            title: form.title,
+            text: label,
+            default,
+            labels: form.labels,
+            timeout: form.timeout,
+        })?;
+
+        match output {
+            Some(buf) => values.push(String::from_utf8(trim_newline(&buf).to_vec())?),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(values))
+}
+
+fn form_zenity(mut command: Command, form: &Form) -> Result<Option<Vec<String>>> {
+    command.arg("--forms");
+
+    command.arg("--title");
+    command.arg(form.title);
+
+    command.arg("--text");
+    command.arg(form.text);
+
+    for (label, _) in &form.fields {
+        command.arg("--add-entry");
+        command.arg(label);
+    }
+
+    // Entries are single lines, so a newline can't be part of a value
+    command.args(["--separator", "\n"]);
+
+    label_arg(&mut command, "--ok-label", form.labels.ok);
+    label_arg(&mut command, "--cancel-label", form.labels.cancel);
+
+    let output = output(&mut command, Backend::Zenity, form.timeout)?;
+
+    match output.status.code() {
+        Some(0) => {
+            let text = String::from_utf8(trim_newline(&output.stdout).to_vec())?;
+            let values: Vec<String> = text.split('\n').map(str::to_string).collect();
+
+            match values.len() == form.fields.len() {
+                true => Ok(Some(values)),
+                false => Err(Error::UnexpectedOutput("zenity")),
+            }
+        }
+        Some(1) => Ok(None),
+        _ => Err(failure("zenity", &output.stderr)),
+    }
+}
+
+struct ImplementationParams<'a> {
+    command: Command,
+    title: &'a str,
+    text: &'a str,
+    default: &'a str,
+    labels: LabelSet<'a>,
+    timeout: Option<Duration>,
+}
+
+fn command_kdialog(params: ImplementationParams) -> Command {
+    let mut command = params.command;
+
+    command.arg("--inputbox");
+    command.arg(params.text);
+    command.arg(params.default);
+
+    command.arg("--title");
+    command.arg(params.title);
+
+    label_arg(&mut command, "--ok-label", params.labels.ok);
+    label_arg(&mut command, "--cancel-label", params.labels.cancel);
+
+    command
+}
+
+fn dialog_implementation_kdialog(params: ImplementationParams) -> Result<Option<Vec<u8>>> {
+    let timeout = params.timeout;
+    let mut command = command_kdialog(params);
+
+    let output = output(&mut command, Backend::KDialog, timeout)?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(output.stdout)),
+        Some(1) => Ok(None),
+        _ => Err(failure("kdialog", &output.stderr)),
+    }
+}
+
+fn command_zenity(params: ImplementationParams) -> Command {
+    let mut command = params.command;
+
+    command.arg("--entry");
+
+    command.arg("--title");
+    command.arg(params.title);
+
+    command.arg("--text");
+    command.arg(params.text);
+
+    command.arg("--entry-text");
+    command.arg(params.default);
+
+    label_arg(&mut command, "--ok-label", params.labels.ok);
+    label_arg(&mut command, "--cancel-label", params.labels.cancel);
+
+    command
+}
+
+fn dialog_implementation_zenity(params: ImplementationParams) -> Result<Option<Vec<u8>>> {
+    let timeout = params.timeout;
+    let mut command = command_zenity(params);
+
+    let output = output(&mut command, Backend::Zenity, timeout)?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(output.stdout)),
+        Some(1) => Ok(None),
+        _ => Err(failure("zenity", &output.stderr)),
+    }
+}
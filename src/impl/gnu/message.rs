@@ -1,33 +1,72 @@
-use super::{should_use, UseCommand};
-use crate::{Dialog, Error, MessageAlert, MessageConfirm, MessageType, Result};
-use std::process::Command;
+use super::{
+    argv, failure, label_arg, output, output_with_input, should_use, with_retry, with_status,
+    UseCommand,
+};
+use crate::{
+    message::{countdown_seconds, escape_markup, show_details, wrap_text, SHOW_DETAILS},
+    r#impl, trace, AskOpenOrCreate, AskSaveChanges, Backend, Busy, BusyHandle, ButtonOrder, Dialog,
+    Error, LabelSet, MessageAlert, MessageConfirm, MessageType, Result, SaveChangesChoice,
+    TextInfo,
+};
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::Duration;
 
 impl Dialog for MessageAlert<'_> {
     type Output = ();
 
-    fn show(self) -> Result<Self::Output> {
-        match should_use() {
-            Some(UseCommand::KDialog(command)) => {
-                dialog_implementation_kdialog(ImplementationParams {
-                    command,
-                    title: self.title,
-                    text: self.text,
-                    typ: self.typ,
-                    ask: false,
-                })?;
-                Ok(())
-            }
-            Some(UseCommand::Zenity(command)) => {
-                dialog_implementation_zenity(ImplementationParams {
-                    command,
-                    title: self.title,
-                    text: self.text,
-                    typ: self.typ,
-                    ask: false,
-                })?;
-                Ok(())
-            }
-            None => Err(Error::NoImplementation),
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("MessageAlert", self.title, || {
+            crate::in_alert_queue(|| {
+                r#impl::backend("MessageAlert", || {
+                    with_retry(self.retry, || match should_use() {
+                        Some(UseCommand::KDialog(command)) => {
+                            dialog_implementation_kdialog(self.params(command))?;
+                            Ok(())
+                        }
+                        Some(UseCommand::Zenity(command)) => {
+                            dialog_implementation_zenity(self.params(command))?;
+                            Ok(())
+                        }
+                        None => Err(Error::NoImplementation),
+                    })
+                })
+            })
+        })
+    }
+}
+
+impl MessageAlert<'_> {
+    /// Also hands back how the zenity or kdialog process exited, to tell apart what the output
+    /// treats the same, like zenity's timeout (5) and cancelling (1).
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        match should_use()? {
+            UseCommand::KDialog(command) => Some(argv(&command_kdialog(self.params(command)))),
+            UseCommand::Zenity(command) => Some(argv(&command_zenity(self.params(command)))),
+        }
+    }
+
+    fn params(&self, command: Command) -> ImplementationParams<'_> {
+        ImplementationParams {
+            command,
+            title: self.title,
+            text: wrap_text(self.text, self.wrap_at),
+            typ: self.typ,
+            markup: self.markup,
+            labels: self.labels,
+            help_link: self.help_link,
+            details: self.details,
+            countdown: None,
+            timeout: self.timeout,
+            ask: false,
         }
     }
 }
@@ -35,99 +74,599 @@ impl Dialog for MessageAlert<'_> {
 impl Dialog for MessageConfirm<'_> {
     type Output = bool;
 
-    fn show(self) -> Result<Self::Output> {
-        match should_use() {
-            Some(UseCommand::KDialog(command)) => {
-                dialog_implementation_kdialog(ImplementationParams {
-                    command,
-                    title: self.title,
-                    text: self.text,
-                    typ: self.typ,
-                    ask: true,
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("MessageConfirm", self.title, || {
+            r#impl::backend("MessageConfirm", || {
+                with_retry(self.retry, || match should_use() {
+                    Some(UseCommand::KDialog(command)) => {
+                        dialog_implementation_kdialog(self.params(command))
+                    }
+                    Some(UseCommand::Zenity(command)) => {
+                        dialog_implementation_zenity(self.params(command))
+                    }
+                    None => Err(Error::NoImplementation),
                 })
-            }
-            Some(UseCommand::Zenity(command)) => {
-                dialog_implementation_zenity(ImplementationParams {
-                    command,
-                    title: self.title,
-                    text: self.text,
-                    typ: self.typ,
-                    ask: true,
+            })
+        })
+    }
+}
+
+impl MessageConfirm<'_> {
+    pub fn show_with_status(self) -> Result<(<Self as Dialog>::Output, Option<ExitStatus>)> {
+        with_status(|| self.show())
+    }
+
+    pub fn debug_command(&self) -> Option<Vec<String>> {
+        match should_use()? {
+            UseCommand::KDialog(command) => Some(argv(&command_kdialog(self.params(command)))),
+            UseCommand::Zenity(command) => Some(argv(&command_zenity(self.params(command)))),
+        }
+    }
+
+    fn params(&self, command: Command) -> ImplementationParams<'_> {
+        ImplementationParams {
+            command,
+            title: self.title,
+            text: wrap_text(self.text, self.wrap_at),
+            typ: self.typ,
+            markup: self.markup,
+            labels: self.labels,
+            help_link: self.help_link,
+            details: self.details,
+            countdown: self.countdown,
+            timeout: self.timeout,
+            ask: true,
+        }
+    }
+}
+
+// Both backends read the content from stdin. kdialog only reads files, so it's pointed at
+// /dev/stdin.
+impl Dialog for TextInfo<'_> {
+    type Output = ();
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("TextInfo", Some(self.title), || {
+            r#impl::backend("TextInfo", || {
+                let (mut command, backend, name) = match should_use() {
+                    Some(UseCommand::KDialog(mut command)) => {
+                        command.args(["--textbox", "/dev/stdin", "600", "400"]);
+                        (command, Backend::KDialog, "kdialog")
+                    }
+                    Some(UseCommand::Zenity(mut command)) => {
+                        command.args(["--text-info", "--width=600", "--height=400"]);
+                        (command, Backend::Zenity, "zenity")
+                    }
+                    None => return Err(Error::NoImplementation),
+                };
+
+                command.arg("--title");
+                command.arg(self.title);
+
+                let input = self.content.clone().into_bytes();
+                let output = output_with_input(&mut command, backend, input, None)?;
+
+                match output.status.code() {
+                    Some(0) | Some(1) => Ok(()),
+                    _ => Err(failure(name, &output.stderr)),
+                }
+            })
+        })
+    }
+}
+
+impl Dialog for AskSaveChanges<'_> {
+    type Output = SaveChangesChoice;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("AskSaveChanges", None, || {
+            r#impl::backend("AskSaveChanges", || match should_use() {
+                Some(UseCommand::KDialog(command)) => {
+                    save_changes_kdialog(command, self.document_name)
+                }
+                Some(UseCommand::Zenity(command)) => {
+                    save_changes_zenity(command, self.document_name)
+                }
+                None => Err(Error::NoImplementation),
+            })
+        })
+    }
+}
+
+impl Dialog for AskOpenOrCreate<'_> {
+    type Output = Option<bool>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("AskOpenOrCreate", self.title, || {
+            r#impl::backend("AskOpenOrCreate", || match should_use() {
+                Some(UseCommand::KDialog(command)) => open_or_create_kdialog(command, self),
+                Some(UseCommand::Zenity(command)) => open_or_create_zenity(command, self),
+                None => Err(Error::NoImplementation),
+            })
+        })
+    }
+}
+
+impl Dialog for Busy<'_> {
+    type Output = BusyHandle;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("Busy", Some(self.title), || {
+            r#impl::backend("Busy", || {
+                let window = match should_use() {
+                    Some(UseCommand::KDialog(command)) => busy_kdialog(command, self)?,
+                    Some(UseCommand::Zenity(command)) => busy_zenity(command, self)?,
+                    None => return Err(Error::NoImplementation),
+                };
+
+                Ok(BusyHandle {
+                    window: Some(window),
                 })
+            })
+        })
+    }
+}
+
+pub(crate) enum BusyWindow {
+    // zenity takes new text on stdin, and is killed to close it
+    Zenity(Child),
+    // kdialog leaves the dialog to a process of its own, which is driven over D-Bus
+    KDialog {
+        qdbus: PathBuf,
+        service: String,
+        path: String,
+    },
+}
+
+impl BusyWindow {
+    pub(crate) fn set_text(&mut self, text: &str) -> Result<()> {
+        match self {
+            BusyWindow::Zenity(child) => {
+                let stdin = child
+                    .stdin
+                    .as_mut()
+                    .ok_or(Error::UnexpectedOutput("zenity"))?;
+                // A line starting with '#' replaces the text, up to the end of the line
+                writeln!(stdin, "# {}", text.replace('\n', " "))?;
+                Ok(())
+            }
+            BusyWindow::KDialog {
+                qdbus,
+                service,
+                path,
+            } => call_qdbus(qdbus, service, path, &["setLabelText", text]),
+        }
+    }
+
+    pub(crate) fn close(self) -> Result<()> {
+        match self {
+            BusyWindow::Zenity(mut child) => {
+                let _ = child.kill();
+                child.wait()?;
+                Ok(())
             }
-            None => Err(Error::NoImplementation),
+            BusyWindow::KDialog {
+                qdbus,
+                service,
+                path,
+            } => call_qdbus(&qdbus, &service, &path, &["close"]),
         }
     }
 }
 
 struct ImplementationParams<'a> {
     command: Command,
-    title: &'a str,
-    text: &'a str,
+    title: Option<&'a str>,
+    text: Cow<'a, str>,
     typ: MessageType,
+    markup: bool,
+    labels: LabelSet<'a>,
+    help_link: Option<(&'a str, &'a str)>,
+    details: Option<&'a str>,
+    countdown: Option<(Duration, bool)>,
+    timeout: Option<Duration>,
     ask: bool,
 }
 
-fn dialog_implementation_kdialog(mut params: ImplementationParams) -> Result<bool> {
-    let command = &mut params.command;
+fn command_kdialog(params: ImplementationParams) -> Command {
+    let mut command = params.command;
+
+    // Only kdialog's error and warning messages have a details button
+    match (params.ask, params.details, params.typ) {
+        (true, _, _) => command.arg("--yesno"),
+        (false, Some(_), MessageType::Error) => command.arg("--detailederror"),
+        (false, Some(_), _) => command.arg("--detailedsorry"),
+        (false, None, _) => command.arg("--msgbox"),
+    };
+
+    let text = match (params.ask, params.details) {
+        (true, Some(details)) if params.markup => {
+            Cow::Owned(format!("{}\n\n{}", params.text, escape_markup(details)))
+        }
+        (true, Some(details)) => Cow::Owned(format!("{}\n\n{}", params.text, details)),
+        _ => params.text,
+    };
+
+    // Qt guesses whether the text is rich text, so escaped text has to be marked as rich text
+    // explicitly for the entities to be decoded
+    match params.help_link {
+        None if params.markup || !text.contains('<') => command.arg(&*text),
+        help_link => {
+            let mut text = match params.markup {
+                true => text.to_string(),
+                false => escape_markup(&text).replace('\n', "<br>"),
+            };
+            if let Some(link) = help_link {
+                text.push_str("<br><br>");
+                text.push_str(&link_markup(link));
+            }
+            command.arg(format!("<qt>{}</qt>", text))
+        }
+    };
+
+    if let (false, Some(details)) = (params.ask, params.details) {
+        command.arg(details);
+    }
+
+    if let Some(icon) = icon_name(params.typ) {
+        command.arg(format!("--icon={}", icon));
+    }
+
+    label_arg(&mut command, "--title", params.title);
 
     if params.ask {
-        command.arg("--yesno");
+        label_arg(&mut command, "--yes-label", params.labels.yes);
+        label_arg(&mut command, "--no-label", params.labels.no);
     } else {
-        command.arg("--msgbox");
+        label_arg(&mut command, "--ok-label", params.labels.ok);
     }
 
-    command.arg(params.text);
+    command
+}
+
+fn icon_name(typ: MessageType) -> Option<&'static str> {
+    match typ {
+        MessageType::Info => Some("dialog-information"),
+        MessageType::Warning => Some("dialog-warning"),
+        MessageType::Error => Some("dialog-error"),
+        MessageType::None => None,
+    }
+}
+
+pub(crate) fn button_order() -> ButtonOrder {
+    match should_use() {
+        Some(UseCommand::KDialog(_)) => ButtonOrder::AffirmativeFirst,
+        _ => ButtonOrder::AffirmativeLast,
+    }
+}
 
-    match params.typ {
-        MessageType::Info => command.arg("--icon=dialog-information"),
-        MessageType::Warning => command.arg("--icon=dialog-warning"),
-        MessageType::Error => command.arg("--icon=dialog-error"),
+pub(crate) fn supports_icon(typ: MessageType) -> bool {
+    match icon_name(typ) {
+        Some(name) => icon_dirs().iter().any(|dir| contains_icon(dir, name, 3)),
+        None => true,
+    }
+}
+
+// Every installed theme is searched rather than just the active one, which would take reading
+// the desktop's own settings to find out.
+fn icon_dirs() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => home.as_ref().map(|home| home.join(".local/share")),
+    };
+    let data_dirs = match env::var("XDG_DATA_DIRS") {
+        Ok(dirs) if !dirs.is_empty() => dirs,
+        _ => "/usr/local/share:/usr/share".to_string(),
     };
 
-    command.arg("--title");
-    command.arg(params.title);
+    let mut dirs: Vec<PathBuf> = vec![];
+    dirs.extend(home.map(|home| home.join(".icons")));
+    dirs.extend(data_home.map(|dir| dir.join("icons")));
+    dirs.extend(data_dirs.split(':').map(|dir| Path::new(dir).join("icons")));
+    dirs.push(PathBuf::from("/usr/share/pixmaps"));
+    dirs
+}
+
+// Themes keep their icons a few folders down, as `<theme>/<size>/<context>/<name>.png`
+fn contains_icon(dir: &Path, name: &str, depth: u32) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
 
-    let output = command.output()?;
+    entries.flatten().any(|entry| {
+        let path = entry.path();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+
+        match (path.file_stem(), extension) {
+            (Some(stem), Some("png" | "svg" | "xpm")) if stem == name => true,
+            _ => depth > 0 && path.is_dir() && contains_icon(&path, name, depth - 1),
+        }
+    })
+}
+
+// Both zenity and kdialog open links in the text with the default browser, without closing the
+// dialog.
+fn link_markup((label, url): (&str, &str)) -> String {
+    format!(
+        "<a href=\"{}\">{}</a>",
+        escape_markup(url),
+        escape_markup(label)
+    )
+}
+
+fn dialog_implementation_kdialog(params: ImplementationParams) -> Result<bool> {
+    // kdialog has no timeout of its own, so the countdown is left to the child process timeout
+    let timeout = match (params.countdown, params.timeout) {
+        (Some((countdown, _)), Some(timeout)) => Some(countdown.min(timeout)),
+        (Some((countdown, _)), None) => Some(countdown),
+        (None, timeout) => timeout,
+    };
+    let countdown = params.countdown;
+
+    let mut command = command_kdialog(params);
+
+    let output = match (output(&mut command, Backend::KDialog, timeout), countdown) {
+        (Err(Error::Timeout(elapsed)), Some((countdown, default))) if elapsed == countdown => {
+            return Ok(default)
+        }
+        (output, _) => output?,
+    };
 
     match output.status.code() {
         Some(0) => Ok(true),
         Some(_) => Ok(false),
-        _ => Err(Error::UnexpectedOutput("kdialog")),
+        _ => Err(failure("kdialog", &output.stderr)),
     }
 }
 
-fn dialog_implementation_zenity(mut params: ImplementationParams) -> Result<bool> {
-    let command = &mut params.command;
+fn command_zenity(params: ImplementationParams) -> Command {
+    let mut command = params.command;
 
     command.arg("--width=400");
 
     if params.ask {
         command.arg("--question");
-        match params.typ {
-            MessageType::Info => command.arg("--icon-name=dialog-information"),
-            MessageType::Warning => command.arg("--icon-name=dialog-warning"),
-            MessageType::Error => command.arg("--icon-name=dialog-error"),
-        };
+        if let Some(icon) = icon_name(params.typ) {
+            command.arg(format!("--icon-name={}", icon));
+        }
     } else {
         match params.typ {
             MessageType::Info => command.arg("--info"),
             MessageType::Warning => command.arg("--warning"),
             MessageType::Error => command.arg("--error"),
+            // zenity has no plain message box, so this still shows its information icon
+            MessageType::None => command.arg("--info"),
         };
     }
 
+    label_arg(&mut command, "--title", params.title);
+
+    command.arg("--text");
+    let mut text = match params.markup {
+        true => params.text.to_string(),
+        false => escape_markup(&params.text).into_owned(),
+    };
+    if let Some(link) = params.help_link {
+        text.push_str("\n\n");
+        text.push_str(&link_markup(link));
+    }
+    command.arg(text);
+
+    if let Some((countdown, _)) = params.countdown {
+        command.arg(format!("--timeout={}", countdown_seconds(countdown)));
+    }
+
+    // The question's "Yes" and "No" are zenity's OK and Cancel buttons
+    if params.ask {
+        label_arg(&mut command, "--ok-label", params.labels.yes);
+        label_arg(&mut command, "--cancel-label", params.labels.no);
+    } else {
+        label_arg(&mut command, "--ok-label", params.labels.ok);
+    }
+
+    if params.details.is_some() {
+        command.args(["--extra-button", SHOW_DETAILS]);
+    }
+
+    command
+}
+
+fn dialog_implementation_zenity(params: ImplementationParams) -> Result<bool> {
+    let (countdown, timeout, details) = (params.countdown, params.timeout, params.details);
+
+    let mut command = command_zenity(params);
+
+    // The details button closes the dialog like the others do, so the same dialog is shown again
+    // once the details have been read
+    loop {
+        let output = output(&mut command, Backend::Zenity, timeout)?;
+
+        match (output.status.code(), countdown, details) {
+            (Some(1), _, Some(details))
+                if String::from_utf8_lossy(&output.stdout).trim() == SHOW_DETAILS =>
+            {
+                show_details(details)?
+            }
+            (Some(5), Some((_, default)), _) => return Ok(default),
+            (Some(0), _, _) => return Ok(true),
+            (Some(_), _, _) => return Ok(false),
+            _ => return Err(failure("zenity", &output.stderr)),
+        }
+    }
+}
+
+// KDE asks to "Save", "Discard" or "Cancel", and kdialog already lays the buttons out that way
+fn save_changes_kdialog(mut command: Command, document_name: &str) -> Result<SaveChangesChoice> {
+    command.arg("--warningyesnocancel");
+    command.arg(format!(
+        "The document \"{}\" has been modified.\nDo you want to save your changes or discard them?",
+        document_name
+    ));
     command.arg("--title");
-    command.arg(params.title);
+    command.arg(document_name);
+    command.args(["--yes-label", "Save", "--no-label", "Discard"]);
+
+    let output = output(&mut command, Backend::KDialog, None)?;
+
+    match output.status.code() {
+        Some(0) => Ok(SaveChangesChoice::Save),
+        Some(1) => Ok(SaveChangesChoice::Discard),
+        Some(2) => Ok(SaveChangesChoice::Cancel),
+        _ => Err(failure("kdialog", &output.stderr)),
+    }
+}
 
+// GNOME asks to "Close without Saving", "Cancel" or "Save". zenity's question only has two buttons
+// of its own, so the third is an extra button, which prints its label instead of setting a
+// different exit code.
+fn save_changes_zenity(mut command: Command, document_name: &str) -> Result<SaveChangesChoice> {
+    const DISCARD: &str = "Close without Saving";
+
+    command.args(["--width=400", "--question", "--icon-name=dialog-warning"]);
+    command.arg("--title");
+    command.arg(document_name);
     command.arg("--text");
-    command.arg(params.text);
+    command.arg(format!(
+        "<b>Save changes to document \u{201c}{}\u{201d} before closing?</b>\n\n\
+         If you don't save, changes will be permanently lost.",
+        escape_markup(document_name)
+    ));
+    command.args(["--ok-label", "Save", "--cancel-label", "Cancel"]);
+    command.args(["--extra-button", DISCARD]);
 
-    let output = command.output()?;
+    let output = output(&mut command, Backend::Zenity, None)?;
 
     match output.status.code() {
-        Some(0) => Ok(true),
-        Some(_) => Ok(false),
-        _ => Err(Error::UnexpectedOutput("zenity")),
+        Some(0) => Ok(SaveChangesChoice::Save),
+        Some(1) if String::from_utf8_lossy(&output.stdout).trim() == DISCARD => {
+            Ok(SaveChangesChoice::Discard)
+        }
+        Some(_) => Ok(SaveChangesChoice::Cancel),
+        _ => Err(failure("zenity", &output.stderr)),
+    }
+}
+
+fn open_or_create_kdialog(mut command: Command, ask: &AskOpenOrCreate) -> Result<Option<bool>> {
+    command.arg("--yesnocancel");
+    command.arg(ask.text);
+    if let Some(title) = ask.title {
+        command.arg("--title");
+        command.arg(title);
+    }
+    command.args([
+        "--yes-label",
+        "Open\u{2026}",
+        "--no-label",
+        "Create New\u{2026}",
+    ]);
+
+    let output = output(&mut command, Backend::KDialog, ask.timeout)?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(true)),
+        Some(1) => Ok(Some(false)),
+        Some(2) => Ok(None),
+        _ => Err(failure("kdialog", &output.stderr)),
+    }
+}
+
+// Same as `save_changes_zenity`, "Create New…" is the extra button
+fn open_or_create_zenity(mut command: Command, ask: &AskOpenOrCreate) -> Result<Option<bool>> {
+    const CREATE: &str = "Create New\u{2026}";
+
+    command.args(["--width=400", "--question", "--no-markup"]);
+    if let Some(title) = ask.title {
+        command.arg("--title");
+        command.arg(title);
+    }
+    command.arg("--text");
+    command.arg(ask.text);
+    command.args(["--ok-label", "Open\u{2026}", "--cancel-label", "Cancel"]);
+    command.args(["--extra-button", CREATE]);
+
+    let output = output(&mut command, Backend::Zenity, ask.timeout)?;
+
+    match output.status.code() {
+        Some(0) => Ok(Some(true)),
+        Some(1) if String::from_utf8_lossy(&output.stdout).trim() == CREATE => Ok(Some(false)),
+        Some(_) => Ok(None),
+        _ => Err(failure("zenity", &output.stderr)),
+    }
+}
+
+fn busy_zenity(mut command: Command, busy: &Busy) -> Result<BusyWindow> {
+    command.args(["--progress", "--pulsate", "--no-cancel"]);
+
+    command.arg("--title");
+    command.arg(busy.title);
+
+    // The starting text is markup, while the text from `set_text` isn't
+    command.arg("--text");
+    command.arg(&*escape_markup(busy.text));
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?command, "spawning dialog");
+
+    let child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|source| Error::BackendSpawnFailed {
+            backend: Backend::Zenity,
+            source,
+        })?;
+
+    Ok(BusyWindow::Zenity(child))
+}
+
+// A progress bar with no steps shows as busy. kdialog prints the D-Bus service and path of the
+// dialog, and exits while the dialog stays up.
+fn busy_kdialog(mut command: Command, busy: &Busy) -> Result<BusyWindow> {
+    let qdbus = ["qdbus", "qdbus6", "qdbus-qt5"]
+        .iter()
+        .find_map(|program| which::which(program).ok())
+        .ok_or(Error::Unsupported {
+            dialog: "Busy",
+            backend: Backend::KDialog,
+        })?;
+
+    command.arg("--progressbar");
+    command.arg(busy.text);
+    command.arg("0");
+
+    command.arg("--title");
+    command.arg(busy.title);
+
+    let output = output(&mut command, Backend::KDialog, None)?;
+    if !output.status.success() {
+        return Err(failure("kdialog", &output.stderr));
+    }
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let mut reference = stdout.split_whitespace();
+    match (reference.next(), reference.next()) {
+        (Some(service), Some(path)) => Ok(BusyWindow::KDialog {
+            qdbus,
+            service: service.to_string(),
+            path: path.to_string(),
+        }),
+        _ => Err(Error::UnexpectedOutput("kdialog")),
+    }
+}
+
+fn call_qdbus(qdbus: &Path, service: &str, path: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(qdbus)
+        .arg(service)
+        .arg(path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(failure("qdbus", &output.stderr)),
     }
 }
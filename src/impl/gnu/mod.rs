@@ -1,41 +1,347 @@
-use crate::{Error, Result};
+use crate::{trace, Backend, Error, NativeDialogHandle, Result};
+use std::cell::Cell;
 use std::env;
-use std::ffi::OsString;
+use std::io::{Read, Write};
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
-mod file;
-mod message;
+pub(crate) mod file;
+mod font;
+mod input;
+pub(crate) mod message;
+mod printer;
+pub(crate) mod shell;
 
 enum UseCommand {
     KDialog(Command),
     Zenity(Command),
 }
 
-fn should_use() -> Option<UseCommand> {
-    let has_display = match env::var("DISPLAY") {
-        Ok(display) if !display.is_empty() => true,
-        _ => false,
-    };
+static BACKEND_PATHS: Mutex<Vec<(Backend, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Runs the backend from `path` instead of looking it up in PATH, for installs elsewhere or stub
+/// programs in tests. Only kdialog, zenity and xdg-open are ever run.
+pub fn set_backend_path(backend: Backend, path: PathBuf) {
+    let mut paths = BACKEND_PATHS.lock().unwrap_or_else(|e| e.into_inner());
+    paths.retain(|(set, _)| *set != backend);
+    paths.push((backend, path));
+}
+
+// Looking a program up walks all of PATH, so whatever was found the first time is remembered for
+// the rest of the process.
+static FOUND_BACKENDS: Mutex<Vec<(Backend, Option<PathBuf>)>> = Mutex::new(Vec::new());
+
+fn backend_path(backend: Backend, program: &str) -> Option<PathBuf> {
+    let paths = BACKEND_PATHS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, path)) = paths.iter().find(|(set, _)| *set == backend) {
+        return Some(path.clone());
+    }
+
+    let mut found = FOUND_BACKENDS.lock().unwrap_or_else(|e| e.into_inner());
+    match found.iter().find(|(looked_up, _)| *looked_up == backend) {
+        Some((_, path)) => path.clone(),
+        None => {
+            let path = which::which(program).ok();
+            found.push((backend, path.clone()));
+            path
+        }
+    }
+}
+
+fn has_display() -> bool {
+    matches!(env::var("DISPLAY"), Ok(display) if !display.is_empty())
+}
+
+#[cfg(not(feature = "mock"))]
+pub(crate) fn can_show_gui() -> bool {
+    has_display()
+        && (backend_path(Backend::Zenity, "zenity").is_some()
+            || backend_path(Backend::KDialog, "kdialog").is_some())
+}
 
-    if has_display {
+fn should_use() -> Option<UseCommand> {
+    if has_display() {
         // Prefer KDialog if the user is logged into a KDE session
-        let kdialog_available = which::which("kdialog").is_ok();
+        let kdialog = backend_path(Backend::KDialog, "kdialog");
 
         if let Ok(desktop) = env::var("XDG_CURRENT_DESKTOP") {
-            if kdialog_available && desktop == "KDE" {
-                return Some(UseCommand::KDialog(Command::new("kdialog")));
+            if let (Some(kdialog), "KDE") = (&kdialog, desktop.as_str()) {
+                trace::backend("kdialog");
+                return Some(UseCommand::KDialog(Command::new(kdialog)));
             }
         }
 
-        if which::which("zenity").is_ok() {
-            return Some(UseCommand::Zenity(Command::new("zenity")));
+        if let Some(zenity) = backend_path(Backend::Zenity, "zenity") {
+            trace::backend("zenity");
+            return Some(UseCommand::Zenity(Command::new(zenity)));
         }
 
-        if kdialog_available {
-            return Some(UseCommand::KDialog(Command::new("kdialog")));
+        if let Some(kdialog) = kdialog {
+            trace::backend("kdialog");
+            return Some(UseCommand::KDialog(Command::new(kdialog)));
         }
     }
 
     None
 }
+
+// The program and arguments a command would run with, for `debug_command`.
+fn argv(command: &Command) -> Vec<String> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn label_arg(command: &mut Command, flag: &str, label: Option<&str>) {
+    if let Some(label) = label {
+        command.arg(flag);
+        command.arg(label);
+    }
+}
+
+fn output(command: &mut Command, backend: Backend, timeout: Option<Duration>) -> Result<Output> {
+    output_native(command, backend, timeout, None)
+}
+
+// `native` is handed the backend's pid as soon as it's running, for `configure_native`.
+fn output_native(
+    command: &mut Command,
+    backend: Backend,
+    timeout: Option<Duration>,
+    native: Option<&(dyn Fn(NativeDialogHandle) + Sync)>,
+) -> Result<Output> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?command, "spawning dialog");
+
+    // Failing to start a backend that was found in PATH means the installation is broken, which
+    // is worth telling apart from the I/O errors that can happen while talking to it.
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| Error::BackendSpawnFailed { backend, source })?;
+
+    if let Some(native) = native {
+        native(NativeDialogHandle::Process(child.id()));
+    }
+
+    let output = wait_for_output(child, timeout)?;
+
+    LAST_STATUS.with(|status| status.set(Some(output.status)));
+    Ok(output)
+}
+
+// `input` is written from its own thread while the child's output is read. A child that's slow
+// to read, or that closes before reading everything, can't block the caller that way.
+fn output_with_input(
+    command: &mut Command,
+    backend: Backend,
+    input: Vec<u8>,
+    timeout: Option<Duration>,
+) -> Result<Output> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?command, input_len = input.len(), "spawning dialog");
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| Error::BackendSpawnFailed { backend, source })?;
+
+    // Dropping the pipe once everything is written lets the child see the end of the input
+    let writer = child.stdin.take().map(|mut stdin| {
+        thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        })
+    });
+
+    let output = wait_for_output(child, timeout);
+
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+
+    let output = output?;
+    LAST_STATUS.with(|status| status.set(Some(output.status)));
+    Ok(output)
+}
+
+thread_local! {
+    static LAST_STATUS: Cell<Option<ExitStatus>> = const { Cell::new(None) };
+}
+
+// For `show_with_status`. When a dialog runs more than one process, as with retries or the
+// overwrite confirmation, this is the status of the last one. Nothing runs with the `mock`
+// feature, or when there's no backend to run, so there's no status then.
+fn with_status<T>(show: impl FnOnce() -> Result<T>) -> Result<(T, Option<ExitStatus>)> {
+    LAST_STATUS.with(|status| status.set(None));
+    let output = show()?;
+    Ok((output, LAST_STATUS.with(Cell::get)))
+}
+
+// Cancelling isn't an error, so only real failures are tried again.
+fn with_retry<T>(retry: Option<(u32, Duration)>, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+    let (count, backoff) = retry.unwrap_or_default();
+
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(error) if attempt < count && error.is_transient() => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(%error, attempt, "retrying dialog");
+
+                attempt += 1;
+                thread::sleep(backoff);
+            }
+            result => return result,
+        }
+    }
+}
+
+// The pipes are drained on their own threads while waiting, so a chatty child can't block on a
+// full pipe before the deadline. A child that's still running at the deadline is killed.
+// Every child is waited on before this returns, even when waiting fails, so none are left behind
+// as zombies. `Child::wait_with_output` gives up without waiting when reading a pipe fails.
+fn wait_for_output(mut child: Child, timeout: Option<Duration>) -> Result<Output> {
+    fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut buf = vec![];
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    }
+
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = match wait_status(&mut child, timeout) {
+        Ok(status) => status,
+        Err(error) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(error);
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn wait_status(child: &mut Child, timeout: Option<Duration>) -> Result<ExitStatus> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return Ok(child.wait()?),
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if Instant::now() >= deadline {
+            return Err(Error::Timeout(timeout));
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// Both tools end their output with a newline. Only that one is removed, since file names and
+// entered text can end in spaces.
+fn trim_newline(buf: &[u8]) -> &[u8] {
+    buf.strip_suffix(b"\n").unwrap_or(buf)
+}
+
+// zenity and kdialog routinely print toolkit warnings to stderr, even when the dialog works just
+// fine. The exit status decides whether a dialog failed; stderr is only used to explain why.
+const BENIGN_STDERR: &[&str] = &[
+    "GtkDialog mapped without a transient parent",
+    "Gtk-Message:",
+    "dbind-WARNING",
+    "AT-SPI",
+    "Failed to load module",
+    "Theme parsing error",
+];
+
+fn failure(backend: &'static str, stderr: &[u8]) -> Error {
+    let stderr = String::from_utf8_lossy(stderr);
+
+    let lines: Vec<&str> = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let errors: Vec<&str> = lines
+        .iter()
+        .copied()
+        .filter(|line| !BENIGN_STDERR.iter().any(|benign| line.contains(benign)))
+        .collect();
+
+    match (errors.is_empty(), lines.is_empty()) {
+        (false, _) => Error::ImplementationError(errors.join("\n")),
+        (true, false) => Error::ImplementationError(lines.join("\n")),
+        (true, true) => Error::UnexpectedOutput(backend),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GTK_WARNINGS: &str = "\
+        (zenity:4242): Gtk-WARNING **: 12:00:00.000: GtkDialog mapped without a transient parent. This is discouraged.\n\
+        (zenity:4242): dbind-WARNING **: 12:00:00.001: AT-SPI: Error retrieving accessibility bus address\n\
+        Gtk-Message: 12:00:00.002: Failed to load module \"canberra-gtk-module\"\n";
+
+    #[test]
+    fn trim_newline_keeps_trailing_spaces() {
+        assert_eq!(trim_newline(b"/tmp/notes \n"), b"/tmp/notes ");
+    }
+
+    #[test]
+    fn trim_newline_removes_only_one_newline() {
+        assert_eq!(trim_newline(b"/tmp/notes\n\n"), b"/tmp/notes\n");
+        assert_eq!(trim_newline(b"/tmp/notes"), b"/tmp/notes");
+    }
+
+    #[test]
+    fn failure_leaves_out_benign_warnings() {
+        let stderr = format!("{}zenity: cannot open display\n", GTK_WARNINGS);
+
+        match failure("zenity", stderr.as_bytes()) {
+            Error::ImplementationError(message) => {
+                assert_eq!(message, "zenity: cannot open display")
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn failure_keeps_warnings_when_nothing_else_explains_it() {
+        match failure("zenity", GTK_WARNINGS.as_bytes()) {
+            Error::ImplementationError(message) => assert_eq!(message.lines().count(), 3),
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn failure_without_stderr_is_unexpected_output() {
+        assert!(matches!(
+            failure("kdialog", b"\n  \n"),
+            Error::UnexpectedOutput("kdialog")
+        ));
+    }
+}
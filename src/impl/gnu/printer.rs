@@ -0,0 +1,24 @@
+use super::{should_use, UseCommand};
+use crate::{r#impl, trace, Backend, Dialog, Error, PickPrinter, PrinterSelection, Result};
+
+// Neither zenity nor kdialog has a printer picker
+impl Dialog for PickPrinter {
+    type Output = Option<PrinterSelection>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("PickPrinter", None, || {
+            r#impl::backend("PickPrinter", || {
+                let backend = match should_use() {
+                    Some(UseCommand::KDialog(_)) => Backend::KDialog,
+                    Some(UseCommand::Zenity(_)) => Backend::Zenity,
+                    None => return Err(Error::NoImplementation),
+                };
+
+                Err(Error::Unsupported {
+                    dialog: "PickPrinter",
+                    backend,
+                })
+            })
+        })
+    }
+}
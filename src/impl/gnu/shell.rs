@@ -0,0 +1,96 @@
+use super::{backend_path, failure, wait_status};
+use crate::{Backend, Error, Result};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+pub(crate) fn reveal(path: &Path) -> Result<()> {
+    // File managers that implement the FileManager1 interface open the parent folder with the
+    // item selected. Anything else only gets the parent folder opened through xdg-open.
+    let selected = which::which("dbus-send").is_ok()
+        && Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.freedesktop.FileManager1",
+                "--type=method_call",
+                "/org/freedesktop/FileManager1",
+                "org.freedesktop.FileManager1.ShowItems",
+            ])
+            .arg(format!("array:string:{}", file_url(path)))
+            .arg("string:")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+
+    if selected {
+        return Ok(());
+    }
+
+    xdg_open(path.parent().unwrap_or(path))
+}
+
+pub(crate) fn open(path: &Path) -> Result<()> {
+    xdg_open(path)
+}
+
+// xdg-open often runs the application itself rather than handing the file off, so it can take
+// as long to exit as the application does. It's only given a moment to report that nothing can
+// open the file, and is reaped in the background after that. Its output isn't captured, since the
+// application would inherit the pipes and could block on them once nothing reads them.
+const HANDLER_WAIT: Duration = Duration::from_millis(500);
+
+fn xdg_open(path: &Path) -> Result<()> {
+    let mut command = match backend_path(Backend::XdgOpen, "xdg-open") {
+        Some(xdg_open) => Command::new(xdg_open),
+        None => return Err(Error::NoImplementation),
+    };
+    command.arg(path);
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|source| Error::BackendSpawnFailed {
+            backend: Backend::XdgOpen,
+            source,
+        })?;
+
+    let status = match wait_status(&mut child, Some(HANDLER_WAIT)) {
+        Ok(status) => status,
+        Err(Error::Timeout(_)) => {
+            thread::spawn(move || child.wait());
+            return Ok(());
+        }
+        Err(error) => {
+            thread::spawn(move || child.wait());
+            return Err(error);
+        }
+    };
+
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(2) => Err(std::io::Error::from(std::io::ErrorKind::NotFound))?,
+        // "no tool found" and "action failed" both come down to having nothing that opens it
+        Some(3) | Some(4) => Err(Error::NoHandler),
+        _ => Err(failure("xdg-open", &[])),
+    }
+}
+
+fn file_url(path: &Path) -> String {
+    let mut url = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                url.push(byte as char)
+            }
+            byte => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    url
+}
@@ -1,18 +1,51 @@
-use crate::{Dialog, Error, OpenMultipleFile, OpenSingleDir, OpenSingleFile, Result};
+use crate::{
+    file::{
+        create_location, filter_groups, item_kind, resolve_shortcut, start_dir, with_save_extension,
+    },
+    r#impl, trace, Dialog, Error, FileFilter, ItemKind, KnownFolder, OpenMultipleFile,
+    OpenSingleDir, OpenSingleFile, OpenSingleItem, Result, SaveFile,
+};
 use osascript::JavaScript;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 impl Dialog for OpenSingleFile<'_> {
     type Output = Option<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        choose_file(ChooseFileParams {
-            multiple: false,
-            dir: self.dir,
-            filter: self.filter,
-            choose_folder: false,
+    fn show_ref(&self) -> Result<Self::Output> {
+        Ok(self.show_with_links()?.map(|(path, _)| path))
+    }
+}
+
+impl OpenSingleFile<'_> {
+    pub(crate) fn show_with_links(&self) -> Result<Option<(PathBuf, Option<PathBuf>)>> {
+        trace::dialog("OpenSingleFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            if let Some(path) = self.single_match() {
+                return Ok(Some((path, None)));
+            }
+            let filters = filter_groups(self.filter, &self.filters, &self.mime_filters, None);
+
+            let path: Option<PathBuf> = r#impl::backend("OpenSingleFile", || {
+                choose_file(ChooseFileParams {
+                    multiple: false,
+                    dir: start_dir(self.dir, self.known_folder).as_deref(),
+                    location_url: self.location_url,
+                    filter: extensions(&filters),
+                    choose_folder: false,
+                    show_package_contents: self.show_package_contents,
+                })
+            })?;
+
+            Ok(path.map(|path| resolve_shortcut(path, self.resolve_shortcuts)))
         })
     }
 }
@@ -20,36 +53,121 @@ impl Dialog for OpenSingleFile<'_> {
 impl Dialog for OpenMultipleFile<'_> {
     type Output = Vec<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        choose_file::<Option<_>>(ChooseFileParams {
-            multiple: true,
-            dir: self.dir,
-            filter: self.filter,
-            choose_folder: false,
+    fn show_ref(&self) -> Result<Self::Output> {
+        let paths = self.show_with_links()?;
+        Ok(paths.into_iter().map(|(path, _)| path).collect())
+    }
+}
+
+impl OpenMultipleFile<'_> {
+    pub(crate) fn show_with_links(&self) -> Result<Vec<(PathBuf, Option<PathBuf>)>> {
+        trace::dialog("OpenMultipleFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            let filters = filter_groups(self.filter, &self.filters, &self.mime_filters, None);
+
+            let paths: Vec<PathBuf> = r#impl::backend("OpenMultipleFile", || {
+                choose_file::<Option<_>>(ChooseFileParams {
+                    multiple: true,
+                    dir: start_dir(self.dir, self.known_folder).as_deref(),
+                    location_url: self.location_url,
+                    filter: extensions(&filters),
+                    choose_folder: false,
+                    show_package_contents: self.show_package_contents,
+                })
+                .map(Option::unwrap_or_default)
+            })?;
+
+            let paths = paths
+                .into_iter()
+                .map(|path| resolve_shortcut(path, self.resolve_shortcuts))
+                .collect();
+            self.finish_selection(paths)
         })
-        .map(|opt| opt.unwrap_or_else(|| vec![]))
     }
 }
 
 impl Dialog for OpenSingleDir<'_> {
     type Output = Option<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        choose_file(ChooseFileParams {
-            multiple: false,
-            dir: self.dir,
-            filter: None,
-            choose_folder: true,
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("OpenSingleDir", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+
+            r#impl::backend("OpenSingleDir", || {
+                choose_file(ChooseFileParams {
+                    multiple: false,
+                    dir: start_dir(self.dir, self.known_folder).as_deref(),
+                    location_url: self.location_url,
+                    filter: None,
+                    choose_folder: true,
+                    show_package_contents: self.show_package_contents,
+                })
+            })
         })
     }
 }
 
+impl Dialog for OpenSingleItem<'_> {
+    type Output = Option<(PathBuf, ItemKind)>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("OpenSingleItem", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            let filters = filter_groups(self.filter, &self.filters, &self.mime_filters, None);
+
+            let path: Option<PathBuf> = r#impl::backend("OpenSingleItem", || {
+                choose_item(ChooseItemParams {
+                    dir: start_dir(self.dir, self.known_folder).as_deref(),
+                    location_url: self.location_url,
+                    filter: extensions(&filters),
+                    show_package_contents: self.show_package_contents,
+                    autosave_position: self.autosave_position,
+                    join_all_spaces: self.join_all_spaces,
+                })
+            })?;
+
+            Ok(path.map(|path| item_kind(resolve_shortcut(path, self.resolve_shortcuts).0)))
+        })
+    }
+}
+
+// `choose file` has no notion of filter groups, so it's given every extension at once. That's
+// already what `merge_filters_default` asks for, so there's no merged group to add.
+fn extensions<'a>(filters: &[FileFilter<'a>]) -> Option<Vec<&'a str>> {
+    match filters.is_empty() {
+        true => None,
+        false => Some(
+            filters
+                .iter()
+                .flat_map(|f| f.extensions.iter().copied())
+                .collect(),
+        ),
+    }
+}
+
 #[derive(Serialize)]
 struct ChooseFileParams<'a> {
     multiple: bool,
     dir: Option<&'a str>,
-    filter: Option<&'a [&'a str]>,
+    location_url: Option<&'a str>,
+    filter: Option<Vec<&'a str>>,
     choose_folder: bool,
+    show_package_contents: bool,
 }
 
 fn choose_file<T: DeserializeOwned>(params: ChooseFileParams) -> Result<T> {
@@ -61,9 +179,12 @@ fn choose_file<T: DeserializeOwned>(params: ChooseFileParams) -> Result<T> {
 
         const options = {
             multipleSelectionsAllowed: $params.multiple,
+            showingPackageContents: $params.show_package_contents,
         };
 
-        if ($params.dir)
+        if ($params.location_url)
+            options.defaultLocation = Path(ObjC.unwrap($.NSURL.URLWithString($params.location_url).path));
+        else if ($params.dir)
             options.defaultLocation = Path($params.dir.replace(/^\~/, app.pathTo('home folder')));
 
         if ($params.filter)
@@ -84,3 +205,149 @@ fn choose_file<T: DeserializeOwned>(params: ChooseFileParams) -> Result<T> {
 
     script.execute_with_params(params).map_err(Error::from)
 }
+
+#[derive(Serialize)]
+struct ChooseItemParams<'a> {
+    dir: Option<&'a str>,
+    location_url: Option<&'a str>,
+    filter: Option<Vec<&'a str>>,
+    show_package_contents: bool,
+    autosave_position: Option<&'a str>,
+    join_all_spaces: Option<bool>,
+}
+
+// `choose file` and `choose folder` only pick one kind of item, so this goes through NSOpenPanel.
+fn choose_item<T: DeserializeOwned>(params: ChooseItemParams) -> Result<T> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        ObjC.import('AppKit');
+
+        const app = Application.currentApplication();
+        app.includeStandardAdditions = true;
+
+        const panel = $.NSOpenPanel.openPanel;
+        panel.canChooseFiles = true;
+        panel.canChooseDirectories = true;
+        panel.allowsMultipleSelection = false;
+        panel.treatsFilePackagesAsDirectories = $params.show_package_contents;
+
+        if ($params.location_url)
+            panel.directoryURL = $.NSURL.URLWithString($params.location_url);
+        else if ($params.dir)
+            panel.directoryURL = $.NSURL.fileURLWithPath($params.dir.replace(/^\~/, app.pathTo('home folder')));
+
+        if ($params.filter)
+            panel.allowedFileTypes = $($params.filter);
+
+        if ($params.autosave_position)
+            panel.setFrameAutosaveName($params.autosave_position);
+
+        if ($params.join_all_spaces !== null)
+            panel.collectionBehavior = $params.join_all_spaces
+                ? $.NSWindowCollectionBehaviorCanJoinAllSpaces
+                : $.NSWindowCollectionBehaviorMoveToActiveSpace;
+
+        $.NSApplication.sharedApplication.activateIgnoringOtherApps(true);
+
+        if (panel.runModal != $.NSModalResponseOK)
+            return null;
+
+        return ObjC.unwrap(panel.URL.path);
+        ",
+    );
+
+    script.execute_with_params(params).map_err(Error::from)
+}
+
+// The save panel always asks before replacing an existing file, so `confirm_overwrite` has
+// nothing left to do here. `choose file name` can't offer filters, so they're left out.
+impl Dialog for SaveFile<'_> {
+    type Output = Option<PathBuf>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("SaveFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+
+            let name = self.suggested_name()?;
+            let path = r#impl::backend("SaveFile", || {
+                choose_file_name(ChooseFileNameParams {
+                    dir: start_dir(self.dir, self.known_folder).as_deref(),
+                    location_url: self.location_url,
+                    name: &name,
+                })
+            })?;
+
+            // Unset, the name is kept as it was typed, since the dialog never showed a filter
+            let filters = filter_groups(None, &self.filters, &self.mime_filters, None);
+            let filter = filters.get(self.default_filter);
+            let strict = self.strict_extension.or(Some(false));
+            Ok(path.map(|path| with_save_extension(path, filter, strict)))
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct ChooseFileNameParams<'a> {
+    dir: Option<&'a str>,
+    location_url: Option<&'a str>,
+    name: &'a str,
+}
+
+fn choose_file_name(params: ChooseFileNameParams) -> Result<Option<PathBuf>> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        const app = Application.currentApplication();
+        app.includeStandardAdditions = true;
+
+        const options = {
+            defaultName: $params.name,
+        };
+
+        if ($params.location_url)
+            options.defaultLocation = Path(ObjC.unwrap($.NSURL.URLWithString($params.location_url).path));
+        else if ($params.dir)
+            options.defaultLocation = Path($params.dir.replace(/^\~/, app.pathTo('home folder')));
+
+        try {
+            return app.chooseFileName(options).toString();
+        } catch (e) {
+            return null;
+        }
+        ",
+    );
+
+    script.execute_with_params(params).map_err(Error::from)
+}
+
+// The standard folders always sit in the home folder, under their English names.
+pub(crate) fn known_folder_path(folder: KnownFolder) -> Option<String> {
+    let home = env::var("HOME").ok()?;
+
+    let name = match folder {
+        KnownFolder::Home => return Some(home),
+        KnownFolder::Desktop => "Desktop",
+        KnownFolder::Documents => "Documents",
+        KnownFolder::Downloads => "Downloads",
+        KnownFolder::Music => "Music",
+        KnownFolder::Pictures => "Pictures",
+        KnownFolder::Videos => "Movies",
+    };
+
+    Some(format!("{}/{}", home, name))
+}
+
+// `choose file` already resolves aliases and symbolic links, so this only matters for the odd
+// link it hands back as is.
+pub(crate) fn shortcut_target(path: &Path) -> Option<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path).ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{r#impl, trace, Backend, Dialog, Error, FontSelection, PickFont, Result};
+
+// NSFontPanel is a floating panel that reports changes as they happen and never returns a choice,
+// so there's nothing for a script to wait on.
+impl Dialog for PickFont {
+    type Output = Option<FontSelection>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("PickFont", None, || {
+            r#impl::backend("PickFont", || {
+                Err(Error::Unsupported {
+                    dialog: "PickFont",
+                    backend: Backend::OsaScript,
+                })
+            })
+        })
+    }
+}
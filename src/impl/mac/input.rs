@@ -0,0 +1,149 @@
+use crate::{r#impl, trace, Dialog, Error, Form, InputText, Result};
+use osascript::JavaScript;
+use serde::Serialize;
+
+impl Dialog for InputText<'_> {
+    type Output = Option<String>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("InputText", Some(self.title), || {
+            r#impl::backend("InputText", || {
+                display_input(DisplayInputParams {
+                    title: self.title,
+                    text: self.text,
+                    default: self.default,
+                    ok: self.labels.ok.unwrap_or("OK"),
+                    cancel: self.labels.cancel.unwrap_or("Cancel"),
+                })
+            })
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct DisplayInputParams<'a> {
+    title: &'a str,
+    text: &'a str,
+    default: &'a str,
+    ok: &'a str,
+    cancel: &'a str,
+}
+
+fn display_input(params: DisplayInputParams) -> Result<Option<String>> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        const app = Application.currentApplication();
+        app.includeStandardAdditions = true;
+
+        const options = {
+            defaultAnswer: $params.default,
+            buttons: [$params.cancel, $params.ok],
+            defaultButton: $params.ok,
+            cancelButton: $params.cancel,
+            withTitle: $params.title,
+        };
+
+        try {
+            return app.displayDialog($params.text, options).textReturned;
+        } catch (e) {
+            return null;
+        }
+        ",
+    );
+
+    script.execute_with_params(params).map_err(Error::from)
+}
+
+// `display dialog` only has the one text field, so the form is an alert with a label and a text
+// field for each of the fields stacked below its text.
+impl Dialog for Form<'_> {
+    type Output = Option<Vec<String>>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("Form", Some(self.title), || {
+            if self.fields.is_empty() {
+                return Ok(Some(vec![]));
+            }
+
+            r#impl::backend("Form", || {
+                display_form(DisplayFormParams {
+                    title: self.title,
+                    text: self.text,
+                    fields: &self.fields,
+                    ok: self.labels.ok.unwrap_or("OK"),
+                    cancel: self.labels.cancel.unwrap_or("Cancel"),
+                    join_all_spaces: self.join_all_spaces,
+                })
+            })
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct DisplayFormParams<'a> {
+    title: &'a str,
+    text: &'a str,
+    fields: &'a [(&'a str, &'a str)],
+    ok: &'a str,
+    cancel: &'a str,
+    join_all_spaces: Option<bool>,
+}
+
+fn display_form(params: DisplayFormParams) -> Result<Option<Vec<String>>> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        ObjC.import('AppKit');
+
+        const width = 300;
+        const rowHeight = 48;
+        const count = $params.fields.length;
+
+        const view = $.NSView.alloc.initWithFrame($.NSMakeRect(0, 0, width, rowHeight * count));
+
+        const inputs = $params.fields.map(([label, value], i) => {
+            const y = rowHeight * (count - 1 - i);
+
+            const caption = $.NSTextField.labelWithString(label);
+            caption.frame = $.NSMakeRect(0, y + 26, width, 18);
+            view.addSubview(caption);
+
+            const input = $.NSTextField.alloc.initWithFrame($.NSMakeRect(0, y + 2, width, 22));
+            input.stringValue = value;
+            view.addSubview(input);
+
+            return input;
+        });
+
+        inputs.forEach((input, i) => {
+            if (i + 1 < inputs.length)
+                input.nextKeyView = inputs[i + 1];
+        });
+
+        const alert = $.NSAlert.alloc.init;
+        alert.messageText = $params.title;
+        alert.informativeText = $params.text;
+        alert.addButtonWithTitle($params.ok);
+        alert.addButtonWithTitle($params.cancel);
+        alert.accessoryView = view;
+
+        if (inputs.length > 0)
+            alert.window.initialFirstResponder = inputs[0];
+
+        if ($params.join_all_spaces !== null)
+            alert.window.collectionBehavior = $params.join_all_spaces
+                ? $.NSWindowCollectionBehaviorCanJoinAllSpaces
+                : $.NSWindowCollectionBehaviorMoveToActiveSpace;
+
+        $.NSApplication.sharedApplication.activateIgnoringOtherApps(true);
+
+        if (alert.runModal != $.NSAlertFirstButtonReturn)
+            return null;
+
+        return inputs.map(input => ObjC.unwrap(input.stringValue));
+        ",
+    );
+
+    script.execute_with_params(params).map_err(Error::from)
+}
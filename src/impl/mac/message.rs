@@ -1,53 +1,377 @@
-use crate::{Dialog, Error, MessageAlert, MessageConfirm, MessageType, Result};
+use crate::{
+    message::{countdown_seconds, plain_text, show_details, wrap_text, SHOW_DETAILS},
+    r#impl, trace, AskOpenOrCreate, AskSaveChanges, Busy, BusyHandle, Dialog, Error, MessageAlert,
+    MessageConfirm, MessageType, Result, SaveChangesChoice, TextInfo,
+};
 use osascript::JavaScript;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+fn temp_path(extension: &str) -> PathBuf {
+    env::temp_dir().join(format!(
+        "native-dialog-{}-{}.{}",
+        process::id(),
+        NEXT_FILE.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
 
 impl Dialog for MessageAlert<'_> {
     type Output = ();
 
-    fn show(self) -> Result<Self::Output> {
-        display_alert(DisplayAlertParams {
-            title: self.title,
-            text: self.text,
-            icon: &get_dialog_icon(self.typ),
-            buttons: &["OK"],
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("MessageAlert", self.title, || {
+            crate::in_alert_queue(|| {
+                r#impl::backend("MessageAlert", || {
+                    let ok = self.labels.ok.unwrap_or("OK");
+
+                    let mut buttons = vec![ok];
+                    if let Some((label, _)) = self.help_link {
+                        buttons.insert(0, label);
+                    }
+                    let text = add_details(
+                        &mut buttons,
+                        &wrap_text(&plain_text(self.text, self.markup), self.wrap_at),
+                        self.details,
+                    );
+
+                    loop {
+                        let button: String = display_alert(DisplayAlertParams {
+                            title: self.title,
+                            text: &text,
+                            icon: get_dialog_icon(self.typ),
+                            buttons: &buttons,
+                            default_button: None,
+                            cancel_button: None,
+                            giving_up_after: None,
+                            timeout_answer: None,
+                            announcement: self.announcement,
+                        })?;
+
+                        if !open_help_link(self.help_link, &button)?
+                            && !open_details(self.details, &button)?
+                        {
+                            return Ok(());
+                        }
+                    }
+                })
+            })
         })
-        .map(|_: String| ())
     }
 }
 
 impl Dialog for MessageConfirm<'_> {
     type Output = bool;
 
-    fn show(self) -> Result<Self::Output> {
-        let button = display_alert(DisplayAlertParams {
-            title: self.title,
-            text: self.text,
-            icon: &get_dialog_icon(self.typ),
-            buttons: &["No", "Yes"],
-        })?;
-
-        match button {
-            Some(t) => Ok(String::eq(&t, "Yes")),
-            None => Ok(false),
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("MessageConfirm", self.title, || {
+            r#impl::backend("MessageConfirm", || {
+                let yes = self.labels.yes.unwrap_or("Yes");
+                let no = self.labels.no.unwrap_or("No");
+
+                let mut buttons = vec![no, yes];
+                if let Some((label, _)) = self.help_link {
+                    buttons.insert(0, label);
+                }
+                let text = add_details(
+                    &mut buttons,
+                    &wrap_text(&plain_text(self.text, self.markup), self.wrap_at),
+                    self.details,
+                );
+
+                loop {
+                    let button: Option<String> = display_alert(DisplayAlertParams {
+                        title: self.title,
+                        text: &text,
+                        icon: get_dialog_icon(self.typ),
+                        buttons: &buttons,
+                        default_button: None,
+                        // Without a cancel button, Escape does nothing at all
+                        cancel_button: match self.ignore_escape {
+                            true => None,
+                            false => Some(no),
+                        },
+                        giving_up_after: self
+                            .countdown
+                            .map(|(duration, _)| countdown_seconds(duration)),
+                        timeout_answer: self.countdown.map(|(_, default)| match default {
+                            true => yes,
+                            false => no,
+                        }),
+                        announcement: self.announcement,
+                    })?;
+
+                    match button {
+                        Some(t)
+                            if open_help_link(self.help_link, &t)?
+                                || open_details(self.details, &t)? =>
+                        {
+                            continue
+                        }
+                        Some(t) => return Ok(t == yes),
+                        None => return Ok(false),
+                    }
+                }
+            })
+        })
+    }
+}
+
+// osascript gets the whole script, parameters included, as a single argument. Content that could
+// be too long for that goes through a temporary file, which the script reads back itself.
+impl Dialog for TextInfo<'_> {
+    type Output = ();
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("TextInfo", Some(self.title), || {
+            r#impl::backend("TextInfo", || {
+                let path = temp_path("txt");
+                fs::write(&path, &self.content)?;
+
+                let result = text_info(TextInfoParams {
+                    title: self.title,
+                    path: &path,
+                    join_all_spaces: self.join_all_spaces,
+                });
+
+                let _ = fs::remove_file(&path);
+                result
+            })
+        })
+    }
+}
+
+// The buttons read "Don't Save", "Cancel", "Save" from left to right, as in every document-based
+// app. `display dialog` has no way to style "Don't Save" as destructive, though.
+impl Dialog for AskSaveChanges<'_> {
+    type Output = SaveChangesChoice;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("AskSaveChanges", None, || {
+            r#impl::backend("AskSaveChanges", || {
+                let (discard, cancel, save) = ("Don't Save", "Cancel", "Save");
+
+                let button: Option<String> = display_alert(DisplayAlertParams {
+                    title: Some(self.document_name),
+                    text: &format!(
+                    "Do you want to save the changes made to the document \u{201c}{}\u{201d}?\n\n\
+                     Your changes will be lost if you don't save them.",
+                    self.document_name
+                ),
+                    icon: Some("caution"),
+                    buttons: &[discard, cancel, save],
+                    default_button: Some(save),
+                    cancel_button: Some(cancel),
+                    giving_up_after: None,
+                    timeout_answer: None,
+                    announcement: None,
+                })?;
+
+                match button.as_deref() {
+                    Some(t) if t == save => Ok(SaveChangesChoice::Save),
+                    Some(t) if t == discard => Ok(SaveChangesChoice::Discard),
+                    _ => Ok(SaveChangesChoice::Cancel),
+                }
+            })
+        })
+    }
+}
+
+impl Dialog for AskOpenOrCreate<'_> {
+    type Output = Option<bool>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("AskOpenOrCreate", self.title, || {
+            r#impl::backend("AskOpenOrCreate", || {
+                let (cancel, create, open) = ("Cancel", "Create New\u{2026}", "Open\u{2026}");
+
+                let button: Option<String> = display_alert(DisplayAlertParams {
+                    title: self.title,
+                    text: self.text,
+                    icon: None,
+                    buttons: &[cancel, create, open],
+                    default_button: Some(open),
+                    cancel_button: Some(cancel),
+                    giving_up_after: None,
+                    timeout_answer: None,
+                    announcement: None,
+                })?;
+
+                match button.as_deref() {
+                    Some(t) if t == open => Ok(Some(true)),
+                    Some(t) if t == create => Ok(Some(false)),
+                    _ => Ok(None),
+                }
+            })
+        })
+    }
+}
+
+// osascript runs in the background while the dialog is up, so the script can't be run through
+// `JavaScript`, which waits for it. The dialog's text is kept in a temporary file that the script
+// checks every so often, and removing the file closes the dialog.
+impl Dialog for Busy<'_> {
+    type Output = BusyHandle;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("Busy", Some(self.title), || {
+            r#impl::backend("Busy", || {
+                let path = temp_path("txt");
+                fs::write(&path, self.text)?;
+
+                let child = Command::new("osascript")
+                    .args(["-l", "JavaScript", "-e", BUSY_SCRIPT])
+                    .arg(self.title)
+                    .arg(&path)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn();
+
+                match child {
+                    Ok(child) => Ok(BusyHandle {
+                        window: Some(BusyWindow { child, path }),
+                    }),
+                    Err(error) => {
+                        let _ = fs::remove_file(&path);
+                        Err(error.into())
+                    }
+                }
+            })
+        })
+    }
+}
+
+pub(crate) struct BusyWindow {
+    child: Child,
+    path: PathBuf,
+}
+
+impl BusyWindow {
+    // Written next to the file and renamed over it, so the script never reads half of it
+    pub(crate) fn set_text(&mut self, text: &str) -> Result<()> {
+        let next = self.path.with_extension("next");
+        fs::write(&next, text)?;
+        fs::rename(&next, &self.path)?;
+        Ok(())
+    }
+
+    // Without the file to tell it to, the script has to be stopped the hard way
+    pub(crate) fn close(mut self) -> Result<()> {
+        let removed = fs::remove_file(&self.path);
+        if removed.is_err() {
+            let _ = self.child.kill();
         }
+        self.child.wait()?;
+        Ok(removed?)
+    }
+}
+
+// language=js
+const BUSY_SCRIPT: &str = r"
+ObjC.import('AppKit');
+
+function run([title, path]) {
+    const read = () => $.NSString.stringWithContentsOfFileEncodingError(path, $.NSUTF8StringEncoding, null);
+
+    const app = $.NSApplication.sharedApplication;
+    app.setActivationPolicy($.NSApplicationActivationPolicyAccessory);
+
+    const window = $.NSPanel.alloc.initWithContentRectStyleMaskBackingDefer(
+        $.NSMakeRect(0, 0, 360, 84), $.NSWindowStyleMaskTitled, $.NSBackingStoreBuffered, false);
+    window.title = title;
+    window.center;
+
+    const label = $.NSTextField.labelWithString(read());
+    label.frame = $.NSMakeRect(20, 44, 320, 20);
+    window.contentView.addSubview(label);
+
+    const indicator = $.NSProgressIndicator.alloc.initWithFrame($.NSMakeRect(20, 20, 320, 20));
+    indicator.indeterminate = true;
+    indicator.startAnimation(null);
+    window.contentView.addSubview(indicator);
+
+    app.activateIgnoringOtherApps(true);
+    window.makeKeyAndOrderFront(null);
+
+    while (true) {
+        const until = $.NSDate.dateWithTimeIntervalSinceNow(0.1);
+        const event = app.nextEventMatchingMaskUntilDateInModeDequeue($.NSEventMaskAny, until, $.NSDefaultRunLoopMode, true);
+        if (!event.isNil())
+            app.sendEvent(event);
+
+        const text = read();
+        if (text.isNil())
+            break;
+        if (!text.isEqualToString(label.stringValue))
+            label.stringValue = text;
+    }
+}
+";
+
+// `display dialog` closes whichever button is clicked, so after opening the link the dialog is
+// shown again to get an answer.
+fn open_help_link(help_link: Option<(&str, &str)>, button: &str) -> Result<bool> {
+    match help_link {
+        Some((label, url)) if label == button => {
+            crate::open_path(Path::new(url))?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+// `display dialog` takes three buttons at most. The details get a button of their own when there's
+// room for one, and go under the text when there isn't.
+fn add_details(buttons: &mut Vec<&str>, text: &str, details: Option<&str>) -> String {
+    match details {
+        Some(_) if buttons.len() < 3 => {
+            buttons.insert(0, SHOW_DETAILS);
+            text.to_string()
+        }
+        Some(details) => format!("{}\n\n{}", text, details),
+        None => text.to_string(),
+    }
+}
+
+// Like the help link, the details button closes the dialog, which is shown again once they've
+// been read.
+fn open_details(details: Option<&str>, button: &str) -> Result<bool> {
+    match details {
+        Some(details) if button == SHOW_DETAILS => {
+            show_details(details)?;
+            Ok(true)
+        }
+        _ => Ok(false),
     }
 }
 
 #[derive(Serialize)]
 struct DisplayAlertParams<'a> {
-    title: &'a str,
+    title: Option<&'a str>,
     text: &'a str,
-    icon: &'a str,
+    icon: Option<&'a str>,
     buttons: &'a [&'a str],
+    default_button: Option<&'a str>,
+    cancel_button: Option<&'a str>,
+    giving_up_after: Option<u64>,
+    timeout_answer: Option<&'a str>,
+    announcement: Option<&'a str>,
 }
 
-fn get_dialog_icon(typ: MessageType) -> String {
+fn get_dialog_icon(typ: MessageType) -> Option<&'static str> {
     match typ {
-        MessageType::Info => "note".into(),
-        MessageType::Warning => "caution".into(),
-        MessageType::Error => "stop".into(),
+        MessageType::Info => Some("note"),
+        MessageType::Warning => Some("caution"),
+        MessageType::Error => Some("stop"),
+        MessageType::None => None,
     }
 }
 
@@ -60,12 +384,42 @@ fn display_alert<T: DeserializeOwned>(params: DisplayAlertParams) -> Result<T> {
 
         const options = {
             buttons: $params.buttons,
-            withTitle: $params.title,
-            withIcon: $params.icon,
         };
 
+        if ($params.title !== null)
+            options.withTitle = $params.title;
+
+        if ($params.icon)
+            options.withIcon = $params.icon;
+
+        if ($params.default_button)
+            options.defaultButton = $params.default_button;
+
+        if ($params.cancel_button)
+            options.cancelButton = $params.cancel_button;
+
+        if ($params.giving_up_after)
+            options.givingUpAfter = $params.giving_up_after;
+
+        // `displayDialog` doesn't return until the dialog is closed, so the announcement is
+        // posted just before, at a priority that keeps the dialog from cutting it off
+        if ($params.announcement) {
+            ObjC.import('AppKit');
+
+            const info = $.NSDictionary.dictionaryWithObjectsForKeys(
+                $([$($params.announcement), $.NSNumber.numberWithInteger($.NSAccessibilityPriorityHigh)]),
+                $([$.NSAccessibilityAnnouncementKey, $.NSAccessibilityPriorityKey]),
+            );
+            $.NSAccessibilityPostNotificationWithUserInfo(
+                $.NSApplication.sharedApplication,
+                $.NSAccessibilityAnnouncementRequestedNotification,
+                info,
+            );
+        }
+
         try {
-            return app.displayDialog($params.text, options).buttonReturned;
+            const result = app.displayDialog($params.text, options);
+            return result.gaveUp ? $params.timeout_answer : result.buttonReturned;
         } catch (e) {
             return null;
         }
@@ -74,3 +428,45 @@ fn display_alert<T: DeserializeOwned>(params: DisplayAlertParams) -> Result<T> {
 
     script.execute_with_params(params).map_err(Error::from)
 }
+
+#[derive(Serialize)]
+struct TextInfoParams<'a> {
+    title: &'a str,
+    path: &'a Path,
+    join_all_spaces: Option<bool>,
+}
+
+fn text_info(params: TextInfoParams) -> Result<()> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        ObjC.import('AppKit');
+
+        const content = $.NSString.stringWithContentsOfFileEncodingError($params.path, $.NSUTF8StringEncoding, null);
+
+        const scroll = $.NSScrollView.alloc.initWithFrame($.NSMakeRect(0, 0, 480, 320));
+        scroll.hasVerticalScroller = true;
+
+        const view = $.NSTextView.alloc.initWithFrame($.NSMakeRect(0, 0, 480, 320));
+        view.editable = false;
+        view.verticallyResizable = true;
+        view.autoresizingMask = $.NSViewWidthSizable;
+        view.string = content;
+        scroll.documentView = view;
+
+        const alert = $.NSAlert.alloc.init;
+        alert.messageText = $params.title;
+        alert.accessoryView = scroll;
+
+        if ($params.join_all_spaces !== null)
+            alert.window.collectionBehavior = $params.join_all_spaces
+                ? $.NSWindowCollectionBehaviorCanJoinAllSpaces
+                : $.NSWindowCollectionBehaviorMoveToActiveSpace;
+
+        $.NSApplication.sharedApplication.activateIgnoringOtherApps(true);
+        alert.runModal;
+        ",
+    );
+
+    script.execute_with_params(params).map_err(Error::from)
+}
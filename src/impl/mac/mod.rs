@@ -1,7 +1,11 @@
 use crate::Error;
 
-mod file;
-mod message;
+pub(crate) mod file;
+mod font;
+mod input;
+pub(crate) mod message;
+mod printer;
+pub(crate) mod shell;
 
 impl From<osascript::Error> for Error {
     fn from(error: osascript::Error) -> Self {
@@ -0,0 +1,53 @@
+use crate::{r#impl, trace, Dialog, Error, PickPrinter, PrinterSelection, Result};
+use osascript::JavaScript;
+use serde::Deserialize;
+
+impl Dialog for PickPrinter {
+    type Output = Option<PrinterSelection>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("PickPrinter", None, || {
+            r#impl::backend("PickPrinter", || {
+                Ok(choose_printer()?.map(|printer| PrinterSelection {
+                    name: printer.name,
+                    is_default: printer.is_default,
+                }))
+            })
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ChosenPrinter {
+    name: String,
+    is_default: bool,
+}
+
+// The panel is run on a copy of the shared print info, so picking a printer here doesn't change
+// the one the application prints to.
+fn choose_printer() -> Result<Option<ChosenPrinter>> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        ObjC.import('AppKit');
+
+        const info = $.NSPrintInfo.sharedPrintInfo.copy;
+        const panel = $.NSPrintPanel.printPanel;
+
+        $.NSApplication.sharedApplication.activateIgnoringOtherApps(true);
+
+        if (panel.runModalWithPrintInfo(info) != $.NSModalResponseOK)
+            return null;
+
+        const name = ObjC.unwrap(info.printer.name);
+        const fallback = $.NSPrintInfo.defaultPrinter;
+
+        return {
+            name: name,
+            is_default: !fallback.isNil() && ObjC.unwrap(fallback.name) === name,
+        };
+        ",
+    );
+
+    script.execute().map_err(Error::from)
+}
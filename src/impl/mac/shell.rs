@@ -0,0 +1,45 @@
+use crate::{Error, Result};
+use osascript::JavaScript;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct ShellParams<'a> {
+    path: &'a Path,
+}
+
+pub(crate) fn reveal(path: &Path) -> Result<()> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        ObjC.import('AppKit');
+
+        const url = $.NSURL.fileURLWithPath($params.path);
+        $.NSWorkspace.sharedWorkspace.activateFileViewerSelectingURLs($([url]));
+        ",
+    );
+
+    script
+        .execute_with_params(ShellParams { path })
+        .map_err(Error::from)
+}
+
+pub(crate) fn open(path: &Path) -> Result<()> {
+    let script = JavaScript::new(
+        // language=js
+        r"
+        ObjC.import('AppKit');
+
+        const url = /^[a-z][a-z0-9+.-]+:/i.test($params.path)
+            ? $.NSURL.URLWithString($params.path)
+            : $.NSURL.fileURLWithPath($params.path);
+
+        return $.NSWorkspace.sharedWorkspace.openURL(url);
+        ",
+    );
+
+    match script.execute_with_params(ShellParams { path })? {
+        true => Ok(()),
+        false => Err(Error::NoHandler),
+    }
+}
@@ -7,9 +7,56 @@ pub(crate) mod gnu;
 #[cfg(target_os = "windows")]
 pub(crate) mod win;
 
+#[cfg(target_os = "macos")]
+pub(crate) use mac::shell;
+
+#[cfg(target_os = "linux")]
+pub(crate) use gnu::shell;
+
+#[cfg(target_os = "windows")]
+pub(crate) use win::shell;
+
+#[cfg(target_os = "macos")]
+pub(crate) use mac::file::{known_folder_path, shortcut_target};
+
+#[cfg(target_os = "linux")]
+pub(crate) use gnu::file::{known_folder_path, shortcut_target};
+
+#[cfg(target_os = "windows")]
+pub(crate) use win::file::{known_folder_path, shortcut_target};
+
+#[cfg(target_os = "macos")]
+pub(crate) use mac::message::BusyWindow;
+
+#[cfg(target_os = "linux")]
+pub(crate) use gnu::message::BusyWindow;
+
+#[cfg(target_os = "windows")]
+pub(crate) use win::input::BusyWindow;
+
 #[allow(dead_code)]
 #[derive(PartialEq)]
 pub(crate) enum OpenDialogTarget {
     File,
     Directory,
+    FileOrDirectory,
+}
+
+// Where a dialog calls into the backend itself. With the `mock` feature, that call alone is
+// replaced by the next mock response, and the rest of the dialog runs as usual.
+#[cfg(not(feature = "mock"))]
+#[inline(always)]
+pub(crate) fn backend<T>(
+    _dialog: &'static str,
+    f: impl FnOnce() -> crate::Result<T>,
+) -> crate::Result<T> {
+    f()
+}
+
+#[cfg(feature = "mock")]
+pub(crate) fn backend<T: crate::mock::FromMock>(
+    dialog: &'static str,
+    _f: impl FnOnce() -> crate::Result<T>,
+) -> crate::Result<T> {
+    crate::mock::respond(dialog)
 }
@@ -1,115 +1,474 @@
+use super::file_dialog::{
+    link_target, open_dialog, save_dialog, FileDialogParams, FileDialogResult,
+};
 use crate::{
-    r#impl::OpenDialogTarget, Dialog, Error, OpenMultipleFile, OpenSingleDir, OpenSingleFile,
-    Result,
+    file::{
+        create_location, filter_groups, item_kind, matching_filter, remember_filter,
+        remembered_filter, resolve_location, resolve_shortcut, with_save_extension,
+    },
+    r#impl::{self, OpenDialogTarget},
+    trace, Dialog, FileDialogView, FileFilter, ItemKind, KnownFolder, NativeDialogHandle,
+    OpenMultipleFile, OpenSingleDir, OpenSingleFile, OpenSingleItem, Result, SaveFile,
 };
-use std::path::PathBuf;
-use wfd::{
-    DialogError, DialogParams, OpenDialogResult, FOS_ALLOWMULTISELECT, FOS_FILEMUSTEXIST,
-    FOS_NOREADONLYRETURN, FOS_OVERWRITEPROMPT, FOS_PATHMUSTEXIST, FOS_PICKFOLDERS,
+use std::path::{Path, PathBuf};
+use winapi::um::shobjidl::{
+    FOS_ALLOWMULTISELECT, FOS_FILEMUSTEXIST, FOS_NODEREFERENCELINKS, FOS_NOREADONLYRETURN,
+    FOS_OVERWRITEPROMPT, FOS_PATHMUSTEXIST, FOS_PICKFOLDERS,
 };
 
 impl Dialog for OpenSingleFile<'_> {
     type Output = Option<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        super::process_init();
+    fn show_ref(&self) -> Result<Self::Output> {
+        Ok(self.show_with_links()?.map(|(path, _)| path))
+    }
+}
+
+impl OpenSingleFile<'_> {
+    pub(crate) fn show_with_links(&self) -> Result<Option<(PathBuf, Option<PathBuf>)>> {
+        trace::dialog("OpenSingleFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            if let Some(path) = self.single_match() {
+                return Ok(Some((path, None)));
+            }
+            let dir = resolve_location(self.dir, self.location_url, self.known_folder)?;
+            let mut merged = vec![];
+            let filters = filter_groups(
+                self.filter,
+                &self.filters,
+                &self.mime_filters,
+                self.merge_filters_default.then_some(&mut merged),
+            );
+
+            let default_filter = remembered_filter(self.remember_filter, &filters);
+
+            let mut selected = None;
+            let path = r#impl::backend("OpenSingleFile", || {
+                super::process_init();
+
+                let result = open_file_dialog(OpenDialogParams {
+                    dir: dir.as_deref(),
+                    filters: &filters,
+                    default_filter,
+                    multiple: false,
+                    target: OpenDialogTarget::File,
+                    persistence_key: client_guid(self.persistence_key, self.autosave_position),
+                    view: self.default_view,
+                    resolve_shortcuts: self.resolve_shortcuts,
+                    native: self.configure_native,
+                })?;
+
+                Ok(result.map(|mut some| {
+                    selected = selected_filter(&some);
+                    some.paths.remove(0)
+                }))
+            })?;
+
+            // The dialog says which filter it ended on, except when it had none or the `mock`
+            // feature answered instead. It's inferred from the chosen file then, as on Linux.
+            if let Some(path) = &path {
+                let index = selected.or_else(|| {
+                    matching_filter(&filters, default_filter, path, self.case_sensitive_filter)
+                });
+                remember_filter(self.remember_filter, index);
+            }
 
-        open_dialog(OpenDialogParams {
-            dir: self.dir,
-            filter: self.filter,
-            multiple: false,
-            target: OpenDialogTarget::File,
+            Ok(path.map(|path| resolve_shortcut(path, self.resolve_shortcuts)))
         })
-        .map(|ok| ok.map(|some| some.selected_file_path))
     }
 }
 
 impl Dialog for OpenMultipleFile<'_> {
     type Output = Vec<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        super::process_init();
+    fn show_ref(&self) -> Result<Self::Output> {
+        let paths = self.show_with_links()?;
+        Ok(paths.into_iter().map(|(path, _)| path).collect())
+    }
+}
+
+impl OpenMultipleFile<'_> {
+    pub(crate) fn show_with_links(&self) -> Result<Vec<(PathBuf, Option<PathBuf>)>> {
+        trace::dialog("OpenMultipleFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            let dir = resolve_location(self.dir, self.location_url, self.known_folder)?;
+            let mut merged = vec![];
+            let filters = filter_groups(
+                self.filter,
+                &self.filters,
+                &self.mime_filters,
+                self.merge_filters_default.then_some(&mut merged),
+            );
+
+            let default_filter = remembered_filter(self.remember_filter, &filters);
 
-        let result = open_dialog(OpenDialogParams {
-            dir: self.dir,
-            filter: self.filter,
-            multiple: true,
-            target: OpenDialogTarget::File,
-        });
+            let mut selected = None;
+            let paths: Vec<PathBuf> = r#impl::backend("OpenMultipleFile", || {
+                super::process_init();
 
-        match result {
-            Ok(Some(t)) => Ok(t.selected_file_paths),
-            Ok(None) => Ok(vec![]),
-            Err(e) => Err(e),
-        }
+                let result = open_file_dialog(OpenDialogParams {
+                    dir: dir.as_deref(),
+                    filters: &filters,
+                    default_filter,
+                    multiple: true,
+                    target: OpenDialogTarget::File,
+                    persistence_key: client_guid(self.persistence_key, self.autosave_position),
+                    view: self.default_view,
+                    resolve_shortcuts: self.resolve_shortcuts,
+                    native: self.configure_native,
+                })?;
+
+                Ok(match result {
+                    Some(some) => {
+                        selected = selected_filter(&some);
+                        some.paths
+                    }
+                    None => vec![],
+                })
+            })?;
+
+            if let Some(path) = paths.first() {
+                let index = selected.or_else(|| {
+                    matching_filter(&filters, default_filter, path, self.case_sensitive_filter)
+                });
+                remember_filter(self.remember_filter, index);
+            }
+
+            let paths = paths
+                .into_iter()
+                .map(|path| resolve_shortcut(path, self.resolve_shortcuts))
+                .collect();
+            self.finish_selection(paths)
+        })
     }
 }
 
 impl Dialog for OpenSingleDir<'_> {
     type Output = Option<PathBuf>;
 
-    fn show(self) -> Result<Self::Output> {
-        super::process_init();
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("OpenSingleDir", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            let dir = resolve_location(self.dir, self.location_url, self.known_folder)?;
+
+            r#impl::backend("OpenSingleDir", || {
+                super::process_init();
+
+                open_file_dialog(OpenDialogParams {
+                    dir: dir.as_deref(),
+                    filters: &[],
+                    default_filter: 0,
+                    multiple: false,
+                    target: OpenDialogTarget::Directory,
+                    persistence_key: client_guid(self.persistence_key, self.autosave_position),
+                    view: self.default_view,
+                    resolve_shortcuts: None,
+                    native: self.configure_native,
+                })
+                .map(|ok| ok.map(|mut some| some.paths.remove(0)))
+            })
+        })
+    }
+}
+
+impl Dialog for OpenSingleItem<'_> {
+    type Output = Option<(PathBuf, ItemKind)>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("OpenSingleItem", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            let dir = resolve_location(self.dir, self.location_url, self.known_folder)?;
+            let mut merged = vec![];
+            let filters = filter_groups(
+                self.filter,
+                &self.filters,
+                &self.mime_filters,
+                self.merge_filters_default.then_some(&mut merged),
+            );
+
+            let path: Option<PathBuf> = r#impl::backend("OpenSingleItem", || {
+                super::process_init();
+
+                open_file_dialog(OpenDialogParams {
+                    dir: dir.as_deref(),
+                    filters: &filters,
+                    default_filter: 0,
+                    multiple: false,
+                    target: OpenDialogTarget::FileOrDirectory,
+                    persistence_key: client_guid(self.persistence_key, self.autosave_position),
+                    view: self.default_view,
+                    resolve_shortcuts: self.resolve_shortcuts,
+                    native: self.configure_native,
+                })
+                .map(|ok| ok.map(|mut some| some.paths.remove(0)))
+            })?;
+
+            Ok(path.map(|path| item_kind(resolve_shortcut(path, self.resolve_shortcuts).0)))
+        })
+    }
+}
+
+impl Dialog for SaveFile<'_> {
+    type Output = Option<PathBuf>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("SaveFile", None, || {
+            create_location(
+                self.dir,
+                self.location_url,
+                self.known_folder,
+                self.create_dir_if_missing,
+            )?;
+            let dir = resolve_location(self.dir, self.location_url, self.known_folder)?;
+            let default_filter = self.checked_default_filter()?;
+            let name = self.suggested_name()?;
+            let filters = filter_groups(None, &self.filters, &self.mime_filters, None);
+
+            self.with_overwrite_confirmation(|| {
+                let mut selected = None;
+                let path = r#impl::backend("SaveFile", || {
+                    super::process_init();
+
+                    let result = save_file_dialog(SaveDialogParams {
+                        dir: dir.as_deref(),
+                        name: &name,
+                        filters: &filters,
+                        default_filter,
+                        strict_extension: self.strict_extension,
+                        persistence_key: client_guid(self.persistence_key, self.autosave_position),
+                        view: self.default_view,
+                        native: self.configure_native,
+                    })?;
+
+                    Ok(result.map(|mut some| {
+                        selected = selected_filter(&some);
+                        some.paths.remove(0)
+                    }))
+                })?;
 
-        open_dialog(OpenDialogParams {
-            dir: self.dir,
-            filter: None,
-            multiple: false,
-            target: OpenDialogTarget::Directory,
+                // Without a filter from the dialog, the starting one is used, as on Linux
+                let filter = filters.get(selected.unwrap_or(default_filter));
+                Ok(path.map(|path| with_save_extension(path, filter, self.strict_extension)))
+            })
         })
-        .map(|ok| ok.map(|some| some.selected_file_path))
     }
 }
 
 struct OpenDialogParams<'a> {
     dir: Option<&'a str>,
-    filter: Option<&'a [&'a str]>,
+    filters: &'a [FileFilter<'a>],
+    default_filter: usize,
     multiple: bool,
     target: OpenDialogTarget,
+    persistence_key: Option<u128>,
+    view: Option<FileDialogView>,
+    resolve_shortcuts: Option<bool>,
+    native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
 }
 
-fn open_dialog(params: OpenDialogParams) -> Result<Option<OpenDialogResult>> {
-    let file_types = match params.filter {
-        Some(filter) => {
-            let types: Vec<String> = filter.iter().map(|s| format!("*.{}", s)).collect();
-            types.join(";")
-        }
-        None => String::new(),
-    };
-    let file_types = match params.filter {
-        Some(_) => vec![("", file_types.as_str())],
-        None => vec![],
-    };
+// The dialog remembers its folder, position and size per client GUID, so a position key gets a
+// GUID of its own. FNV-1a keeps it the same from one run to the next.
+fn client_guid(persistence_key: Option<u128>, autosave_position: Option<&str>) -> Option<u128> {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    persistence_key.or_else(|| {
+        autosave_position.map(|key| {
+            key.bytes().fold(OFFSET, |hash, byte| {
+                (hash ^ byte as u128).wrapping_mul(PRIME)
+            })
+        })
+    })
+}
+
+// The folder to pass to SetDefaultFolder. Without a dir, or with an empty one, it isn't called at
+// all and Windows starts wherever it usually would.
+fn default_folder(dir: Option<&str>) -> Option<&str> {
+    dir.filter(|dir| !dir.is_empty())
+}
+
+fn view_mode(view: FileDialogView) -> u32 {
+    // FOLDERVIEWMODE values, which winapi doesn't have
+    match view {
+        FileDialogView::Icons => 1,
+        FileDialogView::List => 3,
+        FileDialogView::Details => 4,
+        FileDialogView::Tiles => 6,
+    }
+}
+
+// The file type index is one-based, and zero when there were no filters to pick from.
+fn selected_filter(result: &FileDialogResult) -> Option<usize> {
+    (result.file_type_index as usize).checked_sub(1)
+}
+
+fn file_types<'a>(filters: &[FileFilter<'a>]) -> Vec<(&'a str, String)> {
+    filters
+        .iter()
+        .map(|filter| {
+            let types: Vec<String> = filter
+                .extensions
+                .iter()
+                .map(|s| format!("*.{}", s))
+                .collect();
+            (filter.name, types.join(";"))
+        })
+        .collect()
+}
+
+fn open_file_dialog(params: OpenDialogParams) -> Result<Option<FileDialogResult>> {
+    let file_types = file_types(params.filters);
 
     let mut options = FOS_PATHMUSTEXIST | FOS_FILEMUSTEXIST;
     if params.multiple {
         options |= FOS_ALLOWMULTISELECT;
     }
+    // IFileDialog picks either files or folders, so picking both falls back to files
     if params.target == OpenDialogTarget::Directory {
         options |= FOS_PICKFOLDERS;
     }
+    // Any choice about shortcuts is carried out by `resolve_shortcut` instead of the dialog, so
+    // that the link the user picked is still known
+    if params.resolve_shortcuts.is_some() {
+        options |= FOS_NODEREFERENCELINKS;
+    }
 
-    let params = DialogParams {
-        default_folder: params.dir.unwrap_or(""),
-        file_types,
+    open_dialog(FileDialogParams {
+        default_folder: default_folder(params.dir),
+        file_name: "",
+        file_types: &file_types,
+        default_file_type: params.default_filter,
+        default_extension: None,
         options,
-        ..Default::default()
+        un_options: 0,
+        client_guid: params.persistence_key,
+        view_mode: params.view.map(view_mode),
+        native: params.native,
+    })
+}
+
+struct SaveDialogParams<'a> {
+    dir: Option<&'a str>,
+    name: &'a str,
+    filters: &'a [FileFilter<'a>],
+    default_filter: usize,
+    strict_extension: Option<bool>,
+    persistence_key: Option<u128>,
+    view: Option<FileDialogView>,
+    native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
+}
+
+fn save_file_dialog(params: SaveDialogParams) -> Result<Option<FileDialogResult>> {
+    // Without a default extension, the dialog leaves the typed name alone
+    let default_extension = match params.strict_extension {
+        Some(false) => None,
+        _ => params
+            .filters
+            .get(params.default_filter)
+            .and_then(|filter| filter.extensions.first().copied()),
+    };
+
+    save_dialog(FileDialogParams {
+        default_folder: default_folder(params.dir),
+        file_name: params.name,
+        file_types: &file_types(params.filters),
+        default_file_type: params.default_filter,
+        default_extension,
+        options: FOS_PATHMUSTEXIST | FOS_NOREADONLYRETURN,
+        // Replacing an existing file is confirmed by `SaveFile::with_overwrite_confirmation`
+        un_options: FOS_OVERWRITEPROMPT,
+        client_guid: params.persistence_key,
+        view_mode: params.view.map(view_mode),
+        native: params.native,
+    })
+}
+
+// Looked up through the shell, which knows where the user has moved each folder to.
+pub(crate) fn known_folder_path(folder: KnownFolder) -> Option<String> {
+    use std::ptr::null_mut;
+    use std::slice;
+    use winapi::shared::winerror::SUCCEEDED;
+    use winapi::um::combaseapi::CoTaskMemFree;
+    use winapi::um::knownfolders::{
+        FOLDERID_Desktop, FOLDERID_Documents, FOLDERID_Downloads, FOLDERID_Music,
+        FOLDERID_Pictures, FOLDERID_Profile, FOLDERID_Videos,
     };
+    use winapi::um::shlobj::SHGetKnownFolderPath;
 
-    let result = wfd::open_dialog(params);
+    let id = match folder {
+        KnownFolder::Home => &FOLDERID_Profile,
+        KnownFolder::Desktop => &FOLDERID_Desktop,
+        KnownFolder::Documents => &FOLDERID_Documents,
+        KnownFolder::Downloads => &FOLDERID_Downloads,
+        KnownFolder::Music => &FOLDERID_Music,
+        KnownFolder::Pictures => &FOLDERID_Pictures,
+        KnownFolder::Videos => &FOLDERID_Videos,
+    };
 
-    match result {
-        Ok(t) => Ok(Some(t)),
-        Err(e) => match e {
-            DialogError::UserCancelled => Ok(None),
-            DialogError::HResultFailed { error_method, .. } => {
-                Err(Error::ImplementationError(error_method))
+    unsafe {
+        let mut path = null_mut();
+        let hr = SHGetKnownFolderPath(id, 0, null_mut(), &mut path);
+
+        let result = match SUCCEEDED(hr) {
+            true => {
+                let len = (0..).take_while(|&i| *path.add(i) != 0).count();
+                Some(String::from_utf16_lossy(slice::from_raw_parts(path, len)))
             }
-        },
+            false => None,
+        };
+
+        // The buffer has to be freed even when the call fails
+        CoTaskMemFree(path as _);
+        result
     }
 }
 
-#[allow(dead_code)]
-fn save_dialog() {
-    let mut _options = FOS_OVERWRITEPROMPT | FOS_PATHMUSTEXIST | FOS_NOREADONLYRETURN;
+pub(crate) fn shortcut_target(path: &Path) -> Option<PathBuf> {
+    let is_link = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("lnk"));
+    match is_link {
+        true => link_target(path),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::default_folder;
+
+    #[test]
+    fn no_dir_sets_no_default_folder() {
+        assert_eq!(default_folder(None), None);
+    }
+
+    #[test]
+    fn empty_dir_sets_no_default_folder() {
+        assert_eq!(default_folder(Some("")), None);
+    }
+
+    #[test]
+    fn dir_is_the_default_folder() {
+        assert_eq!(default_folder(Some("C:\\Users")), Some("C:\\Users"));
+    }
 }
@@ -0,0 +1,692 @@
+use super::to_wide;
+use crate::{Error, NativeDialogHandle, Result};
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::ptr::null_mut;
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualIID, GUID, REFIID};
+use winapi::shared::minwindef::ULONG;
+use winapi::shared::winerror::{
+    ERROR_CANCELLED, E_NOINTERFACE, E_NOTIMPL, HRESULT, HRESULT_FROM_WIN32, RPC_E_CHANGED_MODE,
+    SUCCEEDED, S_OK,
+};
+use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize};
+use winapi::um::coml2api::STGM_READ;
+use winapi::um::objbase::{COINIT_APARTMENTTHREADED, COINIT_DISABLE_OLE1DDE};
+use winapi::um::objidl::IPersistFile;
+use winapi::um::servprov::IServiceProvider;
+use winapi::um::shobjidl::{
+    IFileDialog, IFileDialogEvents, IFileDialogEventsVtbl, IFileOpenDialog, IFileSaveDialog,
+    IShellItemArray, FDE_OVERWRITE_RESPONSE, FDE_SHAREVIOLATION_RESPONSE,
+};
+use winapi::um::shobjidl_core::{
+    CLSID_FileOpenDialog, CLSID_FileSaveDialog, IShellItem, IShellLinkW,
+    SHCreateItemFromParsingName, ShellLink, SIGDN_FILESYSPATH,
+};
+use winapi::um::shtypes::COMDLG_FILTERSPEC;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::{Class, Interface};
+
+const SFGAO_FILESYSTEM: u32 = 0x4000_0000;
+
+pub(super) struct FileDialogParams<'a> {
+    pub default_folder: Option<&'a str>,
+    pub file_name: &'a str,
+    pub file_types: &'a [(&'a str, String)],
+    // Zero-based, past the last file type picks the last one
+    pub default_file_type: usize,
+    pub default_extension: Option<&'a str>,
+    pub options: u32,
+    pub un_options: u32,
+    pub client_guid: Option<u128>,
+    // A FOLDERVIEWMODE, such as FVM_DETAILS
+    pub view_mode: Option<u32>,
+    pub native: Option<&'a (dyn Fn(NativeDialogHandle) + Sync)>,
+}
+
+pub(super) struct FileDialogResult {
+    pub paths: Vec<PathBuf>,
+    // One-based, zero when there were no file types to pick from
+    pub file_type_index: u32,
+}
+
+pub(super) fn open_dialog(params: FileDialogParams) -> Result<Option<FileDialogResult>> {
+    let _com = ComInit::new()?;
+
+    let dialog: ComPtr<IFileOpenDialog> = create(&CLSID_FileOpenDialog)?;
+    configure(&dialog, &params)?;
+    configure_native(dialog.0 as *mut IFileDialog, &params);
+
+    if !show(&dialog, params.view_mode)? {
+        return Ok(None);
+    }
+
+    let mut items: *mut IShellItemArray = null_mut();
+    com(
+        unsafe { dialog.GetResults(&mut items) },
+        "IFileOpenDialog::GetResults",
+    )?;
+    let items = ComPtr(items);
+
+    let mut count = 0;
+    com(
+        unsafe { items.GetCount(&mut count) },
+        "IShellItemArray::GetCount",
+    )?;
+
+    let mut paths = vec![];
+    for i in 0..count {
+        let mut item: *mut IShellItem = null_mut();
+        com(
+            unsafe { items.GetItemAt(i, &mut item) },
+            "IShellItemArray::GetItemAt",
+        )?;
+        let item = ComPtr(item);
+
+        // Items without a file system path, like "This PC" or files on a phone, are skipped
+        let mut attributes = 0;
+        com(
+            unsafe { item.GetAttributes(SFGAO_FILESYSTEM, &mut attributes) },
+            "IShellItem::GetAttributes",
+        )?;
+        if attributes & SFGAO_FILESYSTEM != 0 {
+            paths.push(file_system_path(&item)?);
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(Error::UnexpectedOutput("IFileDialog"));
+    }
+
+    Ok(Some(FileDialogResult {
+        paths,
+        file_type_index: file_type_index(&dialog)?,
+    }))
+}
+
+pub(super) fn save_dialog(params: FileDialogParams) -> Result<Option<FileDialogResult>> {
+    let _com = ComInit::new()?;
+
+    let dialog: ComPtr<IFileSaveDialog> = create(&CLSID_FileSaveDialog)?;
+    configure(&dialog, &params)?;
+    configure_native(dialog.0 as *mut IFileDialog, &params);
+
+    if !show(&dialog, params.view_mode)? {
+        return Ok(None);
+    }
+
+    let mut item: *mut IShellItem = null_mut();
+    com(
+        unsafe { dialog.GetResult(&mut item) },
+        "IFileDialog::GetResult",
+    )?;
+    let item = ComPtr(item);
+
+    Ok(Some(FileDialogResult {
+        paths: vec![file_system_path(&item)?],
+        file_type_index: file_type_index(&dialog)?,
+    }))
+}
+
+fn configure(dialog: &IFileDialog, params: &FileDialogParams) -> Result<()> {
+    if let Some(guid) = params.client_guid {
+        com(
+            unsafe { dialog.SetClientGuid(&to_guid(guid)) },
+            "IFileDialog::SetClientGuid",
+        )?;
+    }
+
+    if let Some(folder) = params.default_folder {
+        let item = shell_item(folder)?;
+        com(
+            unsafe { dialog.SetDefaultFolder(item.0) },
+            "IFileDialog::SetDefaultFolder",
+        )?;
+    }
+
+    if !params.file_name.is_empty() {
+        let file_name = to_wide(params.file_name);
+        com(
+            unsafe { dialog.SetFileName(file_name.as_ptr()) },
+            "IFileDialog::SetFileName",
+        )?;
+    }
+
+    if !params.file_types.is_empty() {
+        let wide: Vec<(Vec<u16>, Vec<u16>)> = params
+            .file_types
+            .iter()
+            .map(|(name, spec)| (to_wide(name), to_wide(spec)))
+            .collect();
+        let specs: Vec<COMDLG_FILTERSPEC> = wide
+            .iter()
+            .map(|(name, spec)| COMDLG_FILTERSPEC {
+                pszName: name.as_ptr(),
+                pszSpec: spec.as_ptr(),
+            })
+            .collect();
+
+        com(
+            unsafe { dialog.SetFileTypes(specs.len() as u32, specs.as_ptr()) },
+            "IFileDialog::SetFileTypes",
+        )?;
+        let index = clamped_file_type_index(params.default_file_type, specs.len());
+        com(
+            unsafe { dialog.SetFileTypeIndex(index) },
+            "IFileDialog::SetFileTypeIndex",
+        )?;
+    }
+
+    // Names typed without an extension get this one, and it follows the file type as the user
+    // switches between them
+    if let Some(extension) = params.default_extension {
+        let extension = to_wide(extension);
+        com(
+            unsafe { dialog.SetDefaultExtension(extension.as_ptr()) },
+            "IFileDialog::SetDefaultExtension",
+        )?;
+    }
+
+    let mut options = 0;
+    com(
+        unsafe { dialog.GetOptions(&mut options) },
+        "IFileDialog::GetOptions",
+    )?;
+    options = (options & !params.un_options) | params.options;
+    com(
+        unsafe { dialog.SetOptions(options) },
+        "IFileDialog::SetOptions",
+    )?;
+
+    Ok(())
+}
+
+// The dialog is still referenced by the `ComPtr` around the call, so it can't go away while the
+// callback has it
+fn configure_native(dialog: *mut IFileDialog, params: &FileDialogParams) {
+    if let Some(native) = params.native {
+        native(NativeDialogHandle::FileDialog(dialog as *mut _));
+    }
+}
+
+fn show(dialog: &IFileDialog, view_mode: Option<u32>) -> Result<bool> {
+    let events = view_mode.map(ViewModeEvents::new);
+
+    let mut cookie = 0;
+    if let Some(events) = &events {
+        com(
+            unsafe { dialog.Advise(events.0 as *mut IFileDialogEvents, &mut cookie) },
+            "IFileDialog::Advise",
+        )?;
+    }
+
+    let hr = unsafe { dialog.Show(null_mut()) };
+
+    if events.is_some() {
+        unsafe { dialog.Unadvise(cookie) };
+    }
+
+    match hr {
+        _ if hr == HRESULT_FROM_WIN32(ERROR_CANCELLED) => Ok(false),
+        _ => com(hr, "IModalWindow::Show").map(|_| true),
+    }
+}
+
+// SetFileTypeIndex counts from one
+fn clamped_file_type_index(default_file_type: usize, count: usize) -> u32 {
+    (default_file_type.min(count.saturating_sub(1)) + 1) as u32
+}
+
+fn file_type_index(dialog: &IFileDialog) -> Result<u32> {
+    let mut index = 0;
+    com(
+        unsafe { dialog.GetFileTypeIndex(&mut index) },
+        "IFileDialog::GetFileTypeIndex",
+    )?;
+    Ok(index)
+}
+
+fn file_system_path(item: &IShellItem) -> Result<PathBuf> {
+    let mut name = null_mut();
+    com(
+        unsafe { item.GetDisplayName(SIGDN_FILESYSPATH, &mut name) },
+        "IShellItem::GetDisplayName",
+    )?;
+
+    let path = unsafe {
+        let len = (0..).take_while(|&i| *name.offset(i) != 0).count();
+        OsString::from_wide(std::slice::from_raw_parts(name, len))
+    };
+    unsafe { CoTaskMemFree(name as _) };
+
+    Ok(PathBuf::from(path))
+}
+
+// Reads where a `.lnk` file points. The link isn't resolved through the shell, which could go
+// searching for a target that has moved and show UI while doing so.
+pub(super) fn link_target(path: &Path) -> Option<PathBuf> {
+    let _com = ComInit::new().ok()?;
+
+    let link: ComPtr<IShellLinkW> = create(&ShellLink::uuidof()).ok()?;
+    let mut file: *mut IPersistFile = null_mut();
+    com(
+        unsafe {
+            link.QueryInterface(
+                &IPersistFile::uuidof(),
+                &mut file as *mut *mut IPersistFile as *mut _,
+            )
+        },
+        "IUnknown::QueryInterface",
+    )
+    .ok()?;
+    let file = ComPtr(file);
+
+    let path = to_wide(path);
+    com(
+        unsafe { file.Load(path.as_ptr(), STGM_READ) },
+        "IPersistFile::Load",
+    )
+    .ok()?;
+
+    // Links to things other than files, like printers, succeed with an empty path
+    let mut target = vec![0u16; 32768];
+    com(
+        unsafe { link.GetPath(target.as_mut_ptr(), target.len() as i32, null_mut(), 0) },
+        "IShellLinkW::GetPath",
+    )
+    .ok()?;
+
+    let len = target.iter().position(|&c| c == 0).unwrap_or(target.len());
+    match len {
+        0 => None,
+        _ => Some(PathBuf::from(OsString::from_wide(&target[..len]))),
+    }
+}
+
+fn shell_item(path: &str) -> Result<ComPtr<IShellItem>> {
+    let path = to_wide(path);
+    let mut item: *mut IShellItem = null_mut();
+    com(
+        unsafe {
+            SHCreateItemFromParsingName(
+                path.as_ptr(),
+                null_mut(),
+                &IShellItem::uuidof(),
+                &mut item as *mut *mut IShellItem as *mut _,
+            )
+        },
+        "SHCreateItemFromParsingName",
+    )?;
+    Ok(ComPtr(item))
+}
+
+fn create<T: Interface>(class: &GUID) -> Result<ComPtr<T>> {
+    use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+
+    let mut instance: *mut T = null_mut();
+    com(
+        unsafe {
+            CoCreateInstance(
+                class,
+                null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &T::uuidof(),
+                &mut instance as *mut *mut T as *mut _,
+            )
+        },
+        "CoCreateInstance",
+    )?;
+    Ok(ComPtr(instance))
+}
+
+// GUIDs are written most significant field first, so `0x12345678_9abc_def0_...` reads the same
+// way as `{12345678-9ABC-DEF0-...}`.
+fn to_guid(value: u128) -> GUID {
+    GUID {
+        Data1: (value >> 96) as u32,
+        Data2: (value >> 80) as u16,
+        Data3: (value >> 64) as u16,
+        Data4: (value as u64).to_be_bytes(),
+    }
+}
+
+fn com(hr: HRESULT, method: &str) -> Result<()> {
+    match SUCCEEDED(hr) {
+        true => Ok(()),
+        false => Err(Error::ImplementationError(method.to_string())),
+    }
+}
+
+// The folder view only exists once the dialog is up, so the view mode is set when the dialog
+// first shows a folder. It's only set that once, so the user can still switch views.
+#[repr(C)]
+struct ViewModeEvents {
+    vtbl: *const IFileDialogEventsVtbl,
+    refs: Cell<ULONG>,
+    view_mode: u32,
+    applied: Cell<bool>,
+}
+
+static VIEW_MODE_EVENTS_VTBL: IFileDialogEventsVtbl = IFileDialogEventsVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: events_query_interface,
+        AddRef: events_add_ref,
+        Release: events_release,
+    },
+    OnFileOk: events_ignore,
+    OnFolderChanging: events_ignore_item,
+    OnFolderChange: events_folder_change,
+    OnSelectionChange: events_ignore,
+    OnShareViolation: events_share_violation,
+    OnTypeChange: events_ignore,
+    OnOverwrite: events_overwrite,
+};
+
+impl ViewModeEvents {
+    fn new(view_mode: u32) -> ComPtr<ViewModeEvents> {
+        ComPtr(Box::into_raw(Box::new(ViewModeEvents {
+            vtbl: &VIEW_MODE_EVENTS_VTBL,
+            refs: Cell::new(1),
+            view_mode,
+            applied: Cell::new(false),
+        })))
+    }
+}
+
+unsafe extern "system" fn events_query_interface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    object: *mut *mut c_void,
+) -> HRESULT {
+    if IsEqualIID(&*riid, &IUnknown::uuidof()) || IsEqualIID(&*riid, &IFileDialogEvents::uuidof()) {
+        events_add_ref(this);
+        *object = this as *mut c_void;
+        S_OK
+    } else {
+        *object = null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn events_add_ref(this: *mut IUnknown) -> ULONG {
+    let events = &*(this as *const ViewModeEvents);
+    events.refs.set(events.refs.get() + 1);
+    events.refs.get()
+}
+
+unsafe extern "system" fn events_release(this: *mut IUnknown) -> ULONG {
+    let events = &*(this as *const ViewModeEvents);
+    let refs = events.refs.get() - 1;
+    events.refs.set(refs);
+    if refs == 0 {
+        drop(Box::from_raw(this as *mut ViewModeEvents));
+    }
+    refs
+}
+
+unsafe extern "system" fn events_folder_change(
+    this: *mut IFileDialogEvents,
+    dialog: *mut IFileDialog,
+) -> HRESULT {
+    let events = &*(this as *const ViewModeEvents);
+    if events.applied.get() {
+        return S_OK;
+    }
+
+    let mut provider: *mut IServiceProvider = null_mut();
+    let hr = (*dialog).QueryInterface(
+        &IServiceProvider::uuidof(),
+        &mut provider as *mut *mut IServiceProvider as *mut _,
+    );
+    if !SUCCEEDED(hr) {
+        return S_OK;
+    }
+    let provider = ComPtr(provider);
+
+    let mut view: *mut IFolderView = null_mut();
+    let hr = provider.QueryService(
+        &IID_IFOLDERVIEW,
+        &IID_IFOLDERVIEW,
+        &mut view as *mut *mut IFolderView as *mut _,
+    );
+    if SUCCEEDED(hr) {
+        let view = ComPtr(view);
+        if SUCCEEDED(((*view.vtbl).set_current_view_mode)(
+            view.0,
+            events.view_mode,
+        )) {
+            events.applied.set(true);
+        }
+    }
+
+    S_OK
+}
+
+unsafe extern "system" fn events_ignore(
+    _this: *mut IFileDialogEvents,
+    _dialog: *mut IFileDialog,
+) -> HRESULT {
+    S_OK
+}
+
+unsafe extern "system" fn events_ignore_item(
+    _this: *mut IFileDialogEvents,
+    _dialog: *mut IFileDialog,
+    _item: *mut IShellItem,
+) -> HRESULT {
+    S_OK
+}
+
+// E_NOTIMPL leaves the response to the dialog's default handling
+unsafe extern "system" fn events_share_violation(
+    _this: *mut IFileDialogEvents,
+    _dialog: *mut IFileDialog,
+    _item: *mut IShellItem,
+    _response: *mut FDE_SHAREVIOLATION_RESPONSE,
+) -> HRESULT {
+    E_NOTIMPL
+}
+
+unsafe extern "system" fn events_overwrite(
+    _this: *mut IFileDialogEvents,
+    _dialog: *mut IFileDialog,
+    _item: *mut IShellItem,
+    _response: *mut FDE_OVERWRITE_RESPONSE,
+) -> HRESULT {
+    E_NOTIMPL
+}
+
+// winapi doesn't have IFolderView, so just enough of it is declared here to set the view mode.
+// The service that hands it out shares its GUID.
+const IID_IFOLDERVIEW: GUID = GUID {
+    Data1: 0xcde725b0,
+    Data2: 0xccc9,
+    Data3: 0x4519,
+    Data4: [0x91, 0x7e, 0x32, 0x5d, 0x72, 0xfa, 0xb4, 0xce],
+};
+
+#[repr(C)]
+struct IFolderView {
+    vtbl: *const IFolderViewVtbl,
+}
+
+#[repr(C)]
+struct IFolderViewVtbl {
+    parent: IUnknownVtbl,
+    get_current_view_mode: unsafe extern "system" fn(*mut IFolderView, *mut u32) -> HRESULT,
+    set_current_view_mode: unsafe extern "system" fn(*mut IFolderView, u32) -> HRESULT,
+}
+
+struct ComPtr<T>(*mut T);
+
+impl<T> std::ops::Deref for ComPtr<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.0 }
+    }
+}
+
+impl<T> Drop for ComPtr<T> {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { (*(self.0 as *mut IUnknown)).Release() };
+        }
+    }
+}
+
+// COM may already be set up on this thread by the application. When it was set up with a
+// different threading model, the dialog still works, but it isn't ours to uninitialize.
+struct ComInit {
+    initialized: bool,
+}
+
+impl ComInit {
+    fn new() -> Result<Self> {
+        let hr = unsafe {
+            CoInitializeEx(
+                null_mut(),
+                COINIT_APARTMENTTHREADED | COINIT_DISABLE_OLE1DDE,
+            )
+        };
+
+        match hr {
+            RPC_E_CHANGED_MODE => Ok(ComInit { initialized: false }),
+            _ => com(hr, "CoInitializeEx").map(|_| ComInit { initialized: true }),
+        }
+    }
+}
+
+impl Drop for ComInit {
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winapi::shared::winerror::E_FAIL;
+    use winapi::um::objbase::COINIT_MULTITHREADED;
+
+    #[test]
+    fn to_guid_reads_like_the_written_guid() {
+        let guid = to_guid(0x12345678_9abc_def0_1122_334455667788);
+
+        assert_eq!(guid.Data1, 0x12345678);
+        assert_eq!(guid.Data2, 0x9abc);
+        assert_eq!(guid.Data3, 0xdef0);
+        assert_eq!(guid.Data4, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+    }
+
+    #[test]
+    fn file_type_index_counts_from_one() {
+        assert_eq!(clamped_file_type_index(0, 3), 1);
+        assert_eq!(clamped_file_type_index(2, 3), 3);
+    }
+
+    #[test]
+    fn file_type_index_past_the_end_picks_the_last_type() {
+        assert_eq!(clamped_file_type_index(3, 3), 3);
+        assert_eq!(clamped_file_type_index(usize::MAX, 3), 3);
+    }
+
+    #[test]
+    fn file_type_index_without_file_types_is_one() {
+        assert_eq!(clamped_file_type_index(0, 0), 1);
+        assert_eq!(clamped_file_type_index(5, 0), 1);
+    }
+
+    #[test]
+    fn com_names_the_failed_method() {
+        assert!(com(S_OK, "IFileDialog::Show").is_ok());
+        assert!(matches!(
+            com(E_FAIL, "IFileDialog::Show"),
+            Err(Error::ImplementationError(method)) if method == "IFileDialog::Show"
+        ));
+    }
+
+    #[test]
+    fn events_answer_only_their_own_interfaces() {
+        let events = ViewModeEvents::new(0);
+        let this = events.0 as *mut IUnknown;
+
+        let mut object = null_mut();
+        let hr = unsafe { events_query_interface(this, &IFileDialogEvents::uuidof(), &mut object) };
+        assert_eq!(hr, S_OK);
+        assert_eq!(object, this as *mut c_void);
+        assert_eq!(events.refs.get(), 2);
+
+        let hr = unsafe { events_query_interface(this, &IShellItem::uuidof(), &mut object) };
+        assert_eq!(hr, E_NOINTERFACE);
+        assert!(object.is_null());
+        assert_eq!(events.refs.get(), 2);
+
+        unsafe { events_release(this) };
+        assert_eq!(events.refs.get(), 1);
+    }
+
+    #[test]
+    fn com_ptr_releases_its_reference() {
+        let events = ViewModeEvents::new(0);
+        let this = events.0 as *mut IUnknown;
+
+        unsafe { events_add_ref(this) };
+        drop(ComPtr(events.0));
+        assert_eq!(events.refs.get(), 1);
+
+        drop(ComPtr::<ViewModeEvents>(null_mut()));
+    }
+
+    #[test]
+    fn events_leave_other_notifications_to_the_dialog() {
+        let events = ViewModeEvents::new(0);
+        let this = events.0 as *mut IFileDialogEvents;
+
+        unsafe {
+            assert_eq!(events_ignore(this, null_mut()), S_OK);
+            assert_eq!(events_ignore_item(this, null_mut(), null_mut()), S_OK);
+            assert_eq!(
+                events_share_violation(this, null_mut(), null_mut(), null_mut()),
+                E_NOTIMPL
+            );
+            assert_eq!(
+                events_overwrite(this, null_mut(), null_mut(), null_mut()),
+                E_NOTIMPL
+            );
+        }
+        assert!(!events.applied.get());
+    }
+
+    #[test]
+    fn com_init_leaves_another_threading_model_alone() {
+        std::thread::spawn(|| {
+            let hr = unsafe { CoInitializeEx(null_mut(), COINIT_MULTITHREADED) };
+            assert!(SUCCEEDED(hr));
+
+            let init = ComInit::new().unwrap();
+            assert!(!init.initialized);
+            drop(init);
+
+            unsafe { CoUninitialize() };
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn com_init_uninitializes_what_it_initialized() {
+        std::thread::spawn(|| {
+            let outer = ComInit::new().unwrap();
+            let inner = ComInit::new().unwrap();
+            assert!(outer.initialized);
+            assert!(inner.initialized);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{r#impl, trace, Dialog, Error, FontSelection, PickFont, Result};
+
+impl Dialog for PickFont {
+    type Output = Option<FontSelection>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("PickFont", None, || {
+            r#impl::backend("PickFont", || {
+                super::process_init();
+
+                choose_font(self.default.as_ref())
+            })
+        })
+    }
+}
+
+fn choose_font(default: Option<&FontSelection>) -> Result<Option<FontSelection>> {
+    use std::mem::{size_of, zeroed};
+    use std::ptr::null_mut;
+    use winapi::um::commdlg::{
+        ChooseFontW, CommDlgExtendedError, CF_INITTOLOGFONTSTRUCT, CF_SCREENFONTS, CHOOSEFONTW,
+    };
+    use winapi::um::wingdi::{GetDeviceCaps, FW_BOLD, FW_NORMAL, LOGFONTW, LOGPIXELSY};
+    use winapi::um::winuser::{GetDC, ReleaseDC};
+
+    let mut font: LOGFONTW = unsafe { zeroed() };
+    let mut flags = CF_SCREENFONTS;
+
+    // The dialog starts from a LOGFONT, which gives the size in pixels rather than points
+    if let Some(default) = default {
+        // Names too long for the buffer are cut between characters, never inside a surrogate pair
+        let mut family: Vec<u16> = vec![];
+        for c in default.family.chars() {
+            if family.len() + c.len_utf16() > font.lfFaceName.len() - 1 {
+                break;
+            }
+            family.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+        }
+        font.lfFaceName[..family.len()].copy_from_slice(&family);
+
+        let pixels_per_inch = unsafe {
+            let hdc = GetDC(null_mut());
+            let pixels_per_inch = GetDeviceCaps(hdc, LOGPIXELSY);
+            ReleaseDC(null_mut(), hdc);
+            pixels_per_inch
+        };
+        font.lfHeight = -(default.size * pixels_per_inch as f32 / 72.0).round() as i32;
+        font.lfWeight = if default.bold { FW_BOLD } else { FW_NORMAL };
+        font.lfItalic = default.italic as u8;
+
+        flags |= CF_INITTOLOGFONTSTRUCT;
+    }
+
+    let mut params = CHOOSEFONTW {
+        lStructSize: size_of::<CHOOSEFONTW>() as u32,
+        lpLogFont: &mut font,
+        Flags: flags,
+        ..unsafe { zeroed() }
+    };
+    let params_ptr = &mut params as *mut CHOOSEFONTW;
+
+    let ret = super::with_visual_styles(|| unsafe { ChooseFontW(params_ptr) });
+
+    // A zero return is a cancel unless the dialog left an error code behind
+    if ret == 0 {
+        return match unsafe { CommDlgExtendedError() } {
+            0 => Ok(None),
+            code => Err(Error::ImplementationError(format!(
+                "ChooseFontW failed with {}",
+                code
+            ))),
+        };
+    }
+
+    let len = font
+        .lfFaceName
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(font.lfFaceName.len());
+
+    Ok(Some(FontSelection {
+        family: String::from_utf16_lossy(&font.lfFaceName[..len]),
+        size: params.iPointSize as f32 / 10.0,
+        bold: font.lfWeight >= FW_BOLD,
+        italic: font.lfItalic != 0,
+    }))
+}
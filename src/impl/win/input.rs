@@ -0,0 +1,639 @@
+use super::to_wide;
+use crate::{r#impl, trace, Busy, BusyHandle, Dialog, Error, Form, InputText, Result, TextInfo};
+use std::io;
+use std::iter::once;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use winapi::shared::basetsd::INT_PTR;
+use winapi::shared::minwindef::{LOWORD, LPARAM, TRUE, UINT, WPARAM};
+use winapi::shared::windef::HWND;
+
+impl Dialog for InputText<'_> {
+    type Output = Option<String>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("InputText", Some(self.title), || {
+            r#impl::backend("InputText", || {
+                super::process_init();
+
+                let values = input_box(InputBoxParams {
+                    title: self.title,
+                    text: self.text,
+                    fields: &[("", self.default)],
+                    ok: self.labels.ok.unwrap_or("OK"),
+                    cancel: self.labels.cancel.unwrap_or("Cancel"),
+                    monitor: self.monitor,
+                })?;
+
+                Ok(values.map(|mut values| values.remove(0)))
+            })
+        })
+    }
+}
+
+// The input box with a labelled edit control for each field
+impl Dialog for Form<'_> {
+    type Output = Option<Vec<String>>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("Form", Some(self.title), || {
+            if self.fields.is_empty() {
+                return Ok(Some(vec![]));
+            }
+
+            r#impl::backend("Form", || {
+                super::process_init();
+
+                input_box(InputBoxParams {
+                    title: self.title,
+                    text: self.text,
+                    fields: &self.fields,
+                    ok: self.labels.ok.unwrap_or("OK"),
+                    cancel: self.labels.cancel.unwrap_or("Cancel"),
+                    monitor: None,
+                })
+            })
+        })
+    }
+}
+
+// The content is shown in a read-only edit control, which lives here with the input box since
+// both are built from a `DialogTemplate`.
+impl Dialog for TextInfo<'_> {
+    type Output = ();
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("TextInfo", Some(self.title), || {
+            r#impl::backend("TextInfo", || {
+                super::process_init();
+
+                text_info_box(self.title, &self.content)
+            })
+        })
+    }
+}
+
+// The dialog needs its own thread to keep handling messages while the caller works. Like the
+// input box, it's built from a `DialogTemplate`. Marquee progress bars only move with version 6
+// of the common controls, which takes the `windows_visual_styles` feature or a manifest.
+impl Dialog for Busy<'_> {
+    type Output = BusyHandle;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("Busy", Some(self.title), || {
+            r#impl::backend("Busy", || {
+                super::process_init();
+
+                let template = DialogTemplate::busy(self.title);
+                let text = to_wide(self.text);
+
+                let (sender, receiver) = mpsc::channel();
+                let thread = thread::spawn(move || busy_box(template, text, sender));
+
+                // The sender is dropped without sending anything when the dialog can't be created
+                match receiver.recv() {
+                    Ok(hwnd) => Ok(BusyHandle {
+                        window: Some(BusyWindow { hwnd, thread }),
+                    }),
+                    Err(_) => match thread.join() {
+                        Ok(Err(error)) => Err(error.into()),
+                        _ => Err(Error::ImplementationError(
+                            "DialogBoxIndirectParamW".to_string(),
+                        )),
+                    },
+                }
+            })
+        })
+    }
+}
+
+// The window handle is kept as a number, since raw pointers can't be sent between threads
+pub(crate) struct BusyWindow {
+    hwnd: usize,
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl BusyWindow {
+    pub(crate) fn set_text(&mut self, text: &str) -> Result<()> {
+        use winapi::um::winuser::SetDlgItemTextW;
+
+        let text = to_wide(text);
+        match unsafe { SetDlgItemTextW(self.hwnd as HWND, ID_BUSY_TEXT as i32, text.as_ptr()) } {
+            0 => Err(io::Error::last_os_error())?,
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn close(self) -> Result<()> {
+        use winapi::um::winuser::PostMessageW;
+
+        unsafe { PostMessageW(self.hwnd as HWND, WM_CLOSE_BUSY, 0, 0) };
+
+        match self.thread.join() {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(Error::ImplementationError(
+                "the busy dialog's thread panicked".to_string(),
+            )),
+        }
+    }
+}
+
+const ID_BUSY_TEXT: u16 = 200;
+const ID_BUSY_PROGRESS: u16 = 201;
+
+// Sent by `BusyWindow::close`, the only way the dialog closes
+const WM_CLOSE_BUSY: UINT = winapi::um::winuser::WM_APP;
+
+struct BusyState {
+    text: Vec<u16>,
+    sender: Sender<usize>,
+}
+
+fn busy_box(template: Vec<u32>, text: Vec<u16>, sender: Sender<usize>) -> io::Result<()> {
+    use std::mem::size_of;
+    use std::ptr::{null, null_mut};
+    use winapi::um::commctrl::{InitCommonControlsEx, ICC_PROGRESS_CLASS, INITCOMMONCONTROLSEX};
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::winuser::{DialogBoxIndirectParamW, LPCDLGTEMPLATEW};
+
+    let state = BusyState { text, sender };
+    let state_ptr = &state as *const BusyState as LPARAM;
+
+    let ret = super::with_visual_styles(|| unsafe {
+        let controls = INITCOMMONCONTROLSEX {
+            dwSize: size_of::<INITCOMMONCONTROLSEX>() as u32,
+            dwICC: ICC_PROGRESS_CLASS,
+        };
+        InitCommonControlsEx(&controls);
+
+        DialogBoxIndirectParamW(
+            GetModuleHandleW(null()),
+            template.as_ptr() as LPCDLGTEMPLATEW,
+            null_mut(),
+            Some(busy_proc),
+            state_ptr,
+        )
+    });
+
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+unsafe extern "system" fn busy_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> INT_PTR {
+    use winapi::um::commctrl::PBM_SETMARQUEE;
+    use winapi::um::winuser::{
+        EndDialog, GetDlgItem, SendMessageW, SetDlgItemTextW, IDCANCEL, WM_COMMAND, WM_INITDIALOG,
+    };
+
+    match msg {
+        WM_INITDIALOG => {
+            let state = &*(lparam as *const BusyState);
+            SetDlgItemTextW(hwnd, ID_BUSY_TEXT as i32, state.text.as_ptr());
+            SendMessageW(
+                GetDlgItem(hwnd, ID_BUSY_PROGRESS as i32),
+                PBM_SETMARQUEE,
+                TRUE as WPARAM,
+                0,
+            );
+            let _ = state.sender.send(hwnd as usize);
+            TRUE as INT_PTR
+        }
+        WM_CLOSE_BUSY => {
+            EndDialog(hwnd, 0);
+            TRUE as INT_PTR
+        }
+        // Escape and the close button send IDCANCEL, which is ignored
+        WM_COMMAND if LOWORD(wparam as u32) as i32 == IDCANCEL => TRUE as INT_PTR,
+        _ => 0,
+    }
+}
+
+struct InputBoxParams<'a> {
+    title: &'a str,
+    text: &'a str,
+    // A label and a default value for each edit control. Fields without a label go right under
+    // the text.
+    fields: &'a [(&'a str, &'a str)],
+    ok: &'a str,
+    cancel: &'a str,
+    monitor: Option<usize>,
+}
+
+// The edit controls are numbered on from this one
+const ID_EDIT: u16 = 100;
+
+struct InputBoxState {
+    defaults: Vec<Vec<u16>>,
+    monitor: Option<usize>,
+    values: Option<Vec<String>>,
+}
+
+fn input_box(params: InputBoxParams) -> Result<Option<Vec<String>>> {
+    use std::ptr::null;
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::winuser::{DialogBoxIndirectParamW, IDOK, LPCDLGTEMPLATEW};
+
+    let template = DialogTemplate::input_box(&params);
+
+    let mut state = InputBoxState {
+        defaults: params
+            .fields
+            .iter()
+            .map(|(_, default)| to_wide(default))
+            .collect(),
+        monitor: params.monitor,
+        values: None,
+    };
+
+    let state_ptr = &mut state as *mut InputBoxState as LPARAM;
+
+    let ret = super::with_visual_styles(|| unsafe {
+        DialogBoxIndirectParamW(
+            GetModuleHandleW(null()),
+            template.as_ptr() as LPCDLGTEMPLATEW,
+            std::ptr::null_mut(),
+            Some(dialog_proc),
+            state_ptr,
+        )
+    });
+
+    match ret {
+        -1 => Err(std::io::Error::last_os_error())?,
+        x if x == IDOK as INT_PTR => Ok(state.values),
+        _ => Ok(None),
+    }
+}
+
+fn text_info_box(title: &str, content: &str) -> Result<()> {
+    use std::ptr::null;
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::winuser::{DialogBoxIndirectParamW, LPCDLGTEMPLATEW};
+
+    let template = DialogTemplate::text_info(title);
+
+    // Edit controls only break lines at "\r\n"
+    let content = to_wide(content.replace("\r\n", "\n").replace('\n', "\r\n"));
+
+    let ret = super::with_visual_styles(|| unsafe {
+        DialogBoxIndirectParamW(
+            GetModuleHandleW(null()),
+            template.as_ptr() as LPCDLGTEMPLATEW,
+            std::ptr::null_mut(),
+            Some(text_info_proc),
+            content.as_ptr() as LPARAM,
+        )
+    });
+
+    match ret {
+        -1 => Err(std::io::Error::last_os_error())?,
+        _ => Ok(()),
+    }
+}
+
+unsafe extern "system" fn text_info_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> INT_PTR {
+    use winapi::um::winuser::{
+        EndDialog, GetDlgItem, SendMessageW, SetDlgItemTextW, SetFocus, EM_SETLIMITTEXT, IDCANCEL,
+        IDOK, WM_COMMAND, WM_INITDIALOG,
+    };
+
+    match msg {
+        WM_INITDIALOG => {
+            // Lifts the default limit of 32K characters
+            SendMessageW(
+                GetDlgItem(hwnd, ID_EDIT as i32),
+                EM_SETLIMITTEXT as UINT,
+                0,
+                0,
+            );
+            SetDlgItemTextW(hwnd, ID_EDIT as i32, lparam as *const u16);
+            // Focusing the edit control would select all of its text
+            SetFocus(GetDlgItem(hwnd, IDOK));
+            0
+        }
+        WM_COMMAND => {
+            let id = LOWORD(wparam as u32) as i32;
+            if id == IDOK || id == IDCANCEL {
+                EndDialog(hwnd, id as INT_PTR);
+                TRUE as INT_PTR
+            } else {
+                0
+            }
+        }
+        _ => 0,
+    }
+}
+
+unsafe extern "system" fn dialog_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> INT_PTR {
+    use winapi::um::winuser::{
+        EndDialog, GetDlgItem, GetDlgItemTextW, GetWindowLongPtrW, GetWindowTextLengthW,
+        SetDlgItemTextW, SetWindowLongPtrW, GWLP_USERDATA, IDCANCEL, IDOK, WM_COMMAND,
+        WM_INITDIALOG,
+    };
+
+    match msg {
+        WM_INITDIALOG => {
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, lparam);
+            let state = &*(lparam as *const InputBoxState);
+            for (id, default) in (ID_EDIT as i32..).zip(&state.defaults) {
+                SetDlgItemTextW(hwnd, id, default.as_ptr());
+            }
+            if let Some(index) = state.monitor {
+                super::center_on_monitor(hwnd, index);
+            }
+            TRUE as INT_PTR
+        }
+        WM_COMMAND => {
+            let id = LOWORD(wparam as u32) as i32;
+            if id == IDOK {
+                let state = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut InputBoxState);
+                let ids = ID_EDIT as i32..ID_EDIT as i32 + state.defaults.len() as i32;
+                let values = ids.map(|id| {
+                    let len = GetWindowTextLengthW(GetDlgItem(hwnd, id));
+                    let mut buf = vec![0u16; len as usize + 1];
+                    let len = GetDlgItemTextW(hwnd, id, buf.as_mut_ptr(), buf.len() as i32);
+                    String::from_utf16_lossy(&buf[..len as usize])
+                });
+                state.values = Some(values.collect());
+                EndDialog(hwnd, IDOK as INT_PTR);
+                TRUE as INT_PTR
+            } else if id == IDCANCEL {
+                EndDialog(hwnd, IDCANCEL as INT_PTR);
+                TRUE as INT_PTR
+            } else {
+                0
+            }
+        }
+        _ => 0,
+    }
+}
+
+// An in-memory DLGTEMPLATE. Every field in it is a WORD or a DWORD, and entries must start on
+// DWORD boundaries, so the words are later copied into a u32 buffer to get the alignment right.
+struct DialogTemplate {
+    words: Vec<u16>,
+}
+
+impl DialogTemplate {
+    fn input_box(params: &InputBoxParams) -> Vec<u32> {
+        use winapi::um::winuser::{
+            BS_DEFPUSHBUTTON, BS_PUSHBUTTON, ES_AUTOHSCROLL, IDCANCEL, IDOK, SS_LEFT, WS_BORDER,
+            WS_CHILD, WS_TABSTOP, WS_VISIBLE,
+        };
+
+        const WIDTH: i16 = 240;
+        const MARGIN: i16 = 7;
+        const BUTTON_WIDTH: i16 = 50;
+        const BUTTON_HEIGHT: i16 = 14;
+
+        let lines = params.text.lines().count().max(1) as i16;
+        let text_height = lines * 8;
+        let labels = params.fields.iter().filter(|(label, _)| !label.is_empty());
+
+        let fields_height: i16 = params
+            .fields
+            .iter()
+            .map(|(label, _)| if label.is_empty() { 18 } else { 28 })
+            .sum();
+        let button_y = MARGIN + text_height + fields_height + MARGIN;
+        let height = button_y + BUTTON_HEIGHT + MARGIN;
+        let items = 3 + params.fields.len() + labels.count();
+
+        let mut template = DialogTemplate::new(params.title, items as u16, WIDTH, height);
+
+        template.item(
+            SS_LEFT | WS_CHILD | WS_VISIBLE,
+            (MARGIN, MARGIN, WIDTH - 2 * MARGIN, text_height),
+            u16::MAX,
+            0x0082,
+            params.text,
+        );
+
+        let mut y = MARGIN + text_height + 4;
+        for (id, (label, _)) in (ID_EDIT..).zip(params.fields) {
+            if !label.is_empty() {
+                template.item(
+                    SS_LEFT | WS_CHILD | WS_VISIBLE,
+                    (MARGIN, y, WIDTH - 2 * MARGIN, 8),
+                    u16::MAX,
+                    0x0082,
+                    label,
+                );
+                y += 10;
+            }
+            template.item(
+                ES_AUTOHSCROLL | WS_BORDER | WS_TABSTOP | WS_CHILD | WS_VISIBLE,
+                (MARGIN, y, WIDTH - 2 * MARGIN, 14),
+                id,
+                0x0081,
+                "",
+            );
+            y += 18;
+        }
+
+        template.item(
+            BS_DEFPUSHBUTTON | WS_TABSTOP | WS_CHILD | WS_VISIBLE,
+            (
+                WIDTH - 2 * (MARGIN + BUTTON_WIDTH),
+                button_y,
+                BUTTON_WIDTH,
+                BUTTON_HEIGHT,
+            ),
+            IDOK as u16,
+            0x0080,
+            params.ok,
+        );
+        template.item(
+            BS_PUSHBUTTON | WS_TABSTOP | WS_CHILD | WS_VISIBLE,
+            (
+                WIDTH - MARGIN - BUTTON_WIDTH,
+                button_y,
+                BUTTON_WIDTH,
+                BUTTON_HEIGHT,
+            ),
+            IDCANCEL as u16,
+            0x0080,
+            params.cancel,
+        );
+
+        template.finish()
+    }
+
+    fn text_info(title: &str) -> Vec<u32> {
+        use winapi::um::winuser::{
+            BS_DEFPUSHBUTTON, ES_AUTOVSCROLL, ES_MULTILINE, ES_READONLY, IDOK, WS_BORDER, WS_CHILD,
+            WS_TABSTOP, WS_VISIBLE, WS_VSCROLL,
+        };
+
+        const WIDTH: i16 = 320;
+        const MARGIN: i16 = 7;
+        const EDIT_HEIGHT: i16 = 200;
+        const BUTTON_WIDTH: i16 = 50;
+        const BUTTON_HEIGHT: i16 = 14;
+
+        let button_y = MARGIN + EDIT_HEIGHT + MARGIN;
+        let height = button_y + BUTTON_HEIGHT + MARGIN;
+
+        let mut template = DialogTemplate::new(title, 2, WIDTH, height);
+
+        template.item(
+            ES_MULTILINE
+                | ES_READONLY
+                | ES_AUTOVSCROLL
+                | WS_VSCROLL
+                | WS_BORDER
+                | WS_TABSTOP
+                | WS_CHILD
+                | WS_VISIBLE,
+            (MARGIN, MARGIN, WIDTH - 2 * MARGIN, EDIT_HEIGHT),
+            ID_EDIT,
+            0x0081,
+            "",
+        );
+        template.item(
+            BS_DEFPUSHBUTTON | WS_TABSTOP | WS_CHILD | WS_VISIBLE,
+            (
+                WIDTH - MARGIN - BUTTON_WIDTH,
+                button_y,
+                BUTTON_WIDTH,
+                BUTTON_HEIGHT,
+            ),
+            IDOK as u16,
+            0x0080,
+            "OK",
+        );
+
+        template.finish()
+    }
+
+    fn busy(title: &str) -> Vec<u32> {
+        use winapi::um::commctrl::{PBS_MARQUEE, PROGRESS_CLASS};
+        use winapi::um::winuser::{SS_LEFT, WS_CHILD, WS_VISIBLE};
+
+        const WIDTH: i16 = 200;
+        const MARGIN: i16 = 7;
+        const TEXT_HEIGHT: i16 = 16;
+        const BAR_HEIGHT: i16 = 10;
+
+        let bar_y = MARGIN + TEXT_HEIGHT + 4;
+        let height = bar_y + BAR_HEIGHT + MARGIN;
+
+        let mut template = DialogTemplate::new(title, 2, WIDTH, height);
+
+        template.item(
+            SS_LEFT | WS_CHILD | WS_VISIBLE,
+            (MARGIN, MARGIN, WIDTH - 2 * MARGIN, TEXT_HEIGHT),
+            ID_BUSY_TEXT,
+            0x0082,
+            "",
+        );
+        template.named_item(
+            PBS_MARQUEE | WS_CHILD | WS_VISIBLE,
+            (MARGIN, bar_y, WIDTH - 2 * MARGIN, BAR_HEIGHT),
+            ID_BUSY_PROGRESS,
+            PROGRESS_CLASS,
+            "",
+        );
+
+        template.finish()
+    }
+
+    fn new(title: &str, items: u16, width: i16, height: i16) -> Self {
+        use winapi::um::winuser::{
+            DS_CENTER, DS_MODALFRAME, DS_SETFONT, WS_CAPTION, WS_POPUP, WS_SYSMENU,
+        };
+
+        let mut template = DialogTemplate { words: vec![] };
+
+        template.dword(DS_MODALFRAME | DS_CENTER | DS_SETFONT | WS_POPUP | WS_CAPTION | WS_SYSMENU);
+        template.dword(0);
+        template.word(items);
+        template.rect(0, 0, width, height);
+        template.word(0); // menu
+        template.word(0); // class
+        template.string(title);
+        template.word(9);
+        template.string("Segoe UI");
+        template
+    }
+
+    fn finish(mut self) -> Vec<u32> {
+        self.align();
+        self.words
+            .chunks(2)
+            .map(|pair| pair[0] as u32 | (pair[1] as u32) << 16)
+            .collect()
+    }
+
+    fn word(&mut self, value: u16) {
+        self.words.push(value);
+    }
+
+    fn dword(&mut self, value: u32) {
+        self.word(value as u16);
+        self.word((value >> 16) as u16);
+    }
+
+    fn rect(&mut self, x: i16, y: i16, cx: i16, cy: i16) {
+        for value in &[x, y, cx, cy] {
+            self.word(*value as u16);
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.words.extend(value.encode_utf16().chain(once(0)));
+    }
+
+    fn align(&mut self) {
+        if self.words.len() & 1 != 0 {
+            self.word(0);
+        }
+    }
+
+    fn item(&mut self, style: u32, rect: (i16, i16, i16, i16), id: u16, class: u16, title: &str) {
+        self.item_header(style, rect, id);
+        self.word(0xFFFF);
+        self.word(class);
+        self.string(title);
+        self.word(0); // creation data
+    }
+
+    // For controls without a predefined class atom, which are named instead
+    fn named_item(
+        &mut self,
+        style: u32,
+        rect: (i16, i16, i16, i16),
+        id: u16,
+        class: &str,
+        title: &str,
+    ) {
+        self.item_header(style, rect, id);
+        self.string(class);
+        self.string(title);
+        self.word(0); // creation data
+    }
+
+    fn item_header(&mut self, style: u32, rect: (i16, i16, i16, i16), id: u16) {
+        self.align();
+        self.dword(style);
+        self.dword(0);
+        self.rect(rect.0, rect.1, rect.2, rect.3);
+        self.word(id);
+    }
+}
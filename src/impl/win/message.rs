@@ -1,74 +1,619 @@
-use crate::{Dialog, MessageAlert, MessageConfirm, MessageType, Result};
+use super::to_wide;
+use crate::{
+    message::{countdown_seconds, plain_text, wrap_text},
+    r#impl, trace, AskOpenOrCreate, AskSaveChanges, ConfirmDetail, Dialog, LabelSet, MessageAlert,
+    MessageConfirm, MessageType, Result, SaveChangesChoice,
+};
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::time::Duration;
+use winapi::shared::basetsd::{LONG_PTR, UINT_PTR};
+use winapi::shared::minwindef::{DWORD, LPARAM, LRESULT, UINT, WPARAM};
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::HRESULT;
+use winapi::um::winuser::LPHELPINFO;
 
 impl Dialog for MessageAlert<'_> {
     type Output = ();
 
-    fn show(self) -> Result<Self::Output> {
-        super::process_init();
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("MessageAlert", self.title, || {
+            crate::in_alert_queue(|| {
+                r#impl::backend("MessageAlert", || {
+                    super::process_init();
 
-        message_box(MessageBoxParams {
-            title: self.title,
-            text: self.text,
-            typ: self.typ,
-            ask: false,
-        })?;
-        Ok(())
+                    message_box(MessageBoxParams {
+                        title: self.title,
+                        text: &wrap_text(&plain_text(self.text, self.markup), self.wrap_at),
+                        typ: self.typ,
+                        labels: self.labels,
+                        help_link: self.help_link,
+                        details: self.details,
+                        countdown: None,
+                        cancel_on_escape: false,
+                        silent: self.silent,
+                        no_activate: self.no_activate,
+                        monitor: self.monitor,
+                        ask: false,
+                        with_cancel: false,
+                    })?;
+                    Ok(())
+                })
+            })
+        })
     }
 }
 
 impl Dialog for MessageConfirm<'_> {
     type Output = bool;
 
-    fn show(self) -> Result<Self::Output> {
-        super::process_init();
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("MessageConfirm", self.title, || Ok(self.confirm()?.answer))
+    }
+}
+
+impl MessageConfirm<'_> {
+    pub fn show_detailed(self) -> Result<ConfirmDetail> {
+        trace::dialog("MessageConfirm", self.title, || self.confirm())
+    }
+
+    fn confirm(&self) -> Result<ConfirmDetail> {
+        r#impl::backend("MessageConfirm", || {
+            use winapi::um::winuser::IDYES;
+
+            super::process_init();
+
+            let button = message_box(MessageBoxParams {
+                title: self.title,
+                text: &wrap_text(&plain_text(self.text, self.markup), self.wrap_at),
+                typ: self.typ,
+                labels: self.labels,
+                help_link: self.help_link,
+                details: self.details,
+                countdown: self.countdown,
+                cancel_on_escape: !self.ignore_escape,
+                silent: self.silent,
+                no_activate: self.no_activate,
+                monitor: self.monitor,
+                ask: true,
+                with_cancel: false,
+            })?;
 
-        message_box(MessageBoxParams {
-            title: self.title,
-            text: self.text,
-            typ: self.typ,
-            ask: true,
+            Ok(ConfirmDetail {
+                answer: button == IDYES,
+                via_default: Some(WITHOUT_CLICK.with(Cell::get)),
+            })
+        })
+    }
+}
+
+// Windows puts "Save" first and makes it the default, as Notepad and Office do. Message boxes
+// can't style "Don't Save" as destructive.
+impl Dialog for AskSaveChanges<'_> {
+    type Output = SaveChangesChoice;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        use winapi::um::winuser::{IDNO, IDYES};
+
+        trace::dialog("AskSaveChanges", None, || {
+            r#impl::backend("AskSaveChanges", || {
+                super::process_init();
+
+                let button = message_box(MessageBoxParams {
+                    title: Some(self.document_name),
+                    text: &format!("Do you want to save changes to {}?", self.document_name),
+                    typ: MessageType::Warning,
+                    labels: LabelSet {
+                        yes: Some("Save"),
+                        no: Some("Don't Save"),
+                        ..LabelSet::default()
+                    },
+                    help_link: None,
+                    details: None,
+                    countdown: None,
+                    cancel_on_escape: false,
+                    silent: false,
+                    no_activate: false,
+                    monitor: None,
+                    ask: true,
+                    with_cancel: true,
+                })?;
+
+                match button {
+                    IDYES => Ok(SaveChangesChoice::Save),
+                    IDNO => Ok(SaveChangesChoice::Discard),
+                    _ => Ok(SaveChangesChoice::Cancel),
+                }
+            })
+        })
+    }
+}
+
+impl Dialog for AskOpenOrCreate<'_> {
+    type Output = Option<bool>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        use winapi::um::winuser::{IDNO, IDYES};
+
+        trace::dialog("AskOpenOrCreate", self.title, || {
+            r#impl::backend("AskOpenOrCreate", || {
+                super::process_init();
+
+                let button = message_box(MessageBoxParams {
+                    title: self.title,
+                    text: self.text,
+                    typ: MessageType::Info,
+                    labels: LabelSet {
+                        yes: Some("Open..."),
+                        no: Some("Create New..."),
+                        ..LabelSet::default()
+                    },
+                    help_link: None,
+                    details: None,
+                    countdown: None,
+                    cancel_on_escape: false,
+                    silent: false,
+                    no_activate: false,
+                    monitor: None,
+                    ask: true,
+                    with_cancel: true,
+                })?;
+
+                match button {
+                    IDYES => Ok(Some(true)),
+                    IDNO => Ok(Some(false)),
+                    _ => Ok(None),
+                }
+            })
         })
     }
 }
 
 struct MessageBoxParams<'a> {
-    title: &'a str,
+    title: Option<&'a str>,
     text: &'a str,
     typ: MessageType,
+    labels: LabelSet<'a>,
+    help_link: Option<(&'a str, &'a str)>,
+    details: Option<&'a str>,
+    countdown: Option<(Duration, bool)>,
+    cancel_on_escape: bool,
+    silent: bool,
+    no_activate: bool,
+    monitor: Option<usize>,
     ask: bool,
+    with_cancel: bool,
+}
+
+// A message box without a caption is titled "Error" whatever it says, so it gets the name of the
+// executable instead, which is what other windows of the application go by.
+fn app_name() -> std::ffi::OsString {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem().map(ToOwned::to_owned))
+        .unwrap_or_default()
 }
 
-fn message_box(params: MessageBoxParams) -> Result<bool> {
-    use std::ffi::OsStr;
+// Returns the ID of the button that closed the message box
+fn message_box(params: MessageBoxParams) -> Result<i32> {
+    use std::ffi::OsString;
     use std::iter::once;
-    use std::os::windows::ffi::OsStrExt;
-    use std::ptr::null_mut;
+    use std::ptr::{null, null_mut};
+    use winapi::um::processthreadsapi::GetCurrentThreadId;
     use winapi::um::winuser::{
-        MessageBoxW, IDYES, MB_ICONERROR, MB_ICONINFORMATION, MB_ICONWARNING, MB_OK, MB_YESNO,
+        MessageBoxIndirectW, SetWindowsHookExW, UnhookWindowsHookEx, IDCANCEL, IDHELP, IDI_ERROR,
+        IDI_INFORMATION, IDI_WARNING, IDNO, IDOK, IDYES, MB_HELP, MB_ICONERROR, MB_ICONINFORMATION,
+        MB_ICONWARNING, MB_OK, MB_USERICON, MB_YESNO, MB_YESNOCANCEL, MSGBOXPARAMSW, WH_CBT,
+        WH_KEYBOARD,
     };
 
-    let text: Vec<u16> = OsStr::new(params.text)
-        .encode_wide()
-        .chain(once(0))
-        .collect();
+    let text = to_wide(params.text);
+    let caption = to_wide(params.title.map_or_else(app_name, OsString::from));
+    // What a message box shows when there's no task dialog to fold the details away in
+    let text_with_details = params
+        .details
+        .map(|details| to_wide(format!("{}\n\n{}", params.text, details)));
 
-    let caption: Vec<u16> = OsStr::new(params.title)
-        .encode_wide()
-        .chain(once(0))
-        .collect();
+    let (icon, icon_id) = match params.typ {
+        MessageType::Info => (MB_ICONINFORMATION, IDI_INFORMATION),
+        MessageType::Warning => (MB_ICONWARNING, IDI_WARNING),
+        MessageType::Error => (MB_ICONERROR, IDI_ERROR),
+        MessageType::None => (0, null()),
+    };
 
-    let u_type = match params.typ {
-        MessageType::Info => MB_ICONINFORMATION,
-        MessageType::Warning => MB_ICONWARNING,
-        MessageType::Error => MB_ICONERROR,
-    } | if params.ask { MB_YESNO } else { MB_OK };
+    // The sound comes with the predefined icons. The same icon given as a user icon, loaded from
+    // the system since there's no module, shows without it.
+    let (icon, icon_id) = match params.silent && !icon_id.is_null() {
+        true => (MB_USERICON, icon_id),
+        false => (icon, null()),
+    };
 
+    let u_type = icon
+        | match (params.ask, params.with_cancel) {
+            (true, true) => MB_YESNOCANCEL,
+            (true, false) => MB_YESNO,
+            (false, _) => MB_OK,
+        };
+
+    let u_type = match params.help_link {
+        Some(_) => u_type | MB_HELP,
+        None => u_type,
+    };
+
+    let labels: Vec<(i32, String)> = match (params.ask, params.with_cancel) {
+        (true, true) => vec![
+            (IDYES, params.labels.yes),
+            (IDNO, params.labels.no),
+            (IDCANCEL, params.labels.cancel),
+        ],
+        (true, false) => vec![(IDYES, params.labels.yes), (IDNO, params.labels.no)],
+        (false, _) => vec![(IDOK, params.labels.ok)],
+    }
+    .into_iter()
+    .chain(once((IDHELP, params.help_link.map(|(label, _)| label))))
+    .filter_map(|(button, label)| label.map(|label| (button, label.to_string())))
+    .collect();
+
+    // The message box is only reachable from a hook on this thread, which relabels and places it,
+    // holds back its first activation, and then drives the countdown from a timer on the message
+    // box itself.
+    let hook = match labels.is_empty()
+        && params.countdown.is_none()
+        && params.monitor.is_none()
+        && !params.no_activate
+    {
+        false => {
+            HOOK.with(|hook| {
+                *hook.borrow_mut() = Some(BoxHook {
+                    labels,
+                    countdown: params.countdown.map(|(duration, default)| Countdown {
+                        button: if default { IDYES } else { IDNO },
+                        label: String::new(),
+                        remaining: countdown_seconds(duration),
+                    }),
+                    monitor: params.monitor,
+                    no_activate: params.no_activate,
+                    started: false,
+                })
+            });
+            unsafe {
+                SetWindowsHookExW(
+                    WH_CBT,
+                    Some(message_box_hook),
+                    null_mut(),
+                    GetCurrentThreadId(),
+                )
+            }
+        }
+        true => null_mut(),
+    };
+
+    // Confirmations watch the keyboard, both for Escape and to tell whether they were answered
+    // without a click
+    ESCAPE_ANSWERS_NO.with(|escape| escape.set(params.cancel_on_escape));
+    WITHOUT_CLICK.with(|without_click| without_click.set(false));
+    let key_hook = match params.ask {
+        true => unsafe {
+            SetWindowsHookExW(
+                WH_KEYBOARD,
+                Some(key_hook),
+                null_mut(),
+                GetCurrentThreadId(),
+            )
+        },
+        false => null_mut(),
+    };
+
+    // The Help button doesn't close the message box, it only calls back into `open_help_link`
+    HELP_URL.with(|url| *url.borrow_mut() = params.help_link.map(|(_, url)| url.to_string()));
+
+    let box_params = MSGBOXPARAMSW {
+        cbSize: std::mem::size_of::<MSGBOXPARAMSW>() as UINT,
+        hwndOwner: null_mut(),
+        hInstance: null_mut(),
+        lpszText: text_with_details.as_ref().unwrap_or(&text).as_ptr(),
+        lpszCaption: caption.as_ptr(),
+        dwStyle: u_type,
+        lpszIcon: icon_id,
+        dwContextHelpId: 0,
+        lpfnMsgBoxCallback: Some(open_help_link),
+        dwLanguageId: 0,
+    };
+
+    // The hooks above work on a task dialog the same way, since its buttons have the same IDs
     let ret = super::with_visual_styles(|| unsafe {
-        MessageBoxW(null_mut(), text.as_ptr(), caption.as_ptr(), u_type)
+        let task = params
+            .details
+            .and_then(|details| task_dialog(&params, &text, &caption, details));
+
+        match task {
+            Some(ret) => ret,
+            None => match MessageBoxIndirectW(&box_params) {
+                0 => Err(std::io::Error::last_os_error().into()),
+                x => Ok(x),
+            },
+        }
     });
 
-    match ret {
-        0 => Err(std::io::Error::last_os_error())?,
-        x => Ok(x == IDYES),
+    HELP_URL.with(|url| url.borrow_mut().take());
+
+    if !hook.is_null() {
+        unsafe { UnhookWindowsHookEx(hook) };
+        HOOK.with(|hook| hook.borrow_mut().take());
     }
+
+    if !key_hook.is_null() {
+        unsafe { UnhookWindowsHookEx(key_hook) };
+    }
+
+    ret
+}
+
+// Task dialogs come with version 6 of the common controls, so they're looked up at runtime rather
+// than linked, which would keep processes without it from starting at all. Returns `None` when
+// there's no task dialog to show.
+unsafe fn task_dialog(
+    params: &MessageBoxParams,
+    text: &[u16],
+    caption: &[u16],
+    details: &str,
+) -> Option<Result<i32>> {
+    use std::mem::{size_of, transmute, zeroed};
+    use std::ptr::{null, null_mut};
+    use winapi::shared::minwindef::BOOL;
+    use winapi::shared::winerror::SUCCEEDED;
+    use winapi::um::commctrl::{
+        TASKDIALOGCONFIG_u1, TASKDIALOGCONFIG, TDCBF_CANCEL_BUTTON, TDCBF_NO_BUTTON,
+        TDCBF_OK_BUTTON, TDCBF_YES_BUTTON, TDF_ENABLE_HYPERLINKS, TDF_USE_HICON_MAIN,
+        TD_ERROR_ICON, TD_INFORMATION_ICON, TD_WARNING_ICON,
+    };
+    use winapi::um::libloaderapi::{FreeLibrary, GetProcAddress, LoadLibraryW};
+    use winapi::um::winuser::{LoadIconW, IDI_ERROR, IDI_INFORMATION, IDI_WARNING};
+
+    type TaskDialogIndirect = unsafe extern "system" fn(
+        *const TASKDIALOGCONFIG,
+        *mut i32,
+        *mut i32,
+        *mut BOOL,
+    ) -> HRESULT;
+
+    let comctl32 = LoadLibraryW(to_wide("comctl32.dll").as_ptr());
+    if comctl32.is_null() {
+        return None;
+    }
+    let function = GetProcAddress(comctl32, b"TaskDialogIndirect\0".as_ptr() as _);
+    if function.is_null() {
+        FreeLibrary(comctl32);
+        return None;
+    }
+    let function: TaskDialogIndirect = transmute(function);
+
+    let (icon, icon_id) = match params.typ {
+        MessageType::Info => (TD_INFORMATION_ICON, IDI_INFORMATION),
+        MessageType::Warning => (TD_WARNING_ICON, IDI_WARNING),
+        MessageType::Error => (TD_ERROR_ICON, IDI_ERROR),
+        MessageType::None => (null_mut(), null()),
+    };
+
+    // As with message boxes, the sound comes with the predefined icons, and the same icon loaded
+    // from the system shows without it
+    let mut main_icon: TASKDIALOGCONFIG_u1 = zeroed();
+    let flags = match params.silent && !icon_id.is_null() {
+        true => {
+            *main_icon.hMainIcon_mut() = LoadIconW(null_mut(), icon_id);
+            TDF_USE_HICON_MAIN
+        }
+        false => {
+            *main_icon.pszMainIcon_mut() = icon;
+            0
+        }
+    };
+
+    // A task dialog has no Help button, so the help link goes in the footer
+    let footer = params
+        .help_link
+        .map(|(label, _)| to_wide(format!("<a href=\"help\">{}</a>", label)));
+    let flags = match footer {
+        Some(_) => flags | TDF_ENABLE_HYPERLINKS,
+        None => flags,
+    };
+
+    let details = to_wide(details);
+
+    let config = TASKDIALOGCONFIG {
+        cbSize: size_of::<TASKDIALOGCONFIG>() as UINT,
+        dwFlags: flags,
+        dwCommonButtons: match (params.ask, params.with_cancel) {
+            (true, true) => TDCBF_YES_BUTTON | TDCBF_NO_BUTTON | TDCBF_CANCEL_BUTTON,
+            (true, false) => TDCBF_YES_BUTTON | TDCBF_NO_BUTTON,
+            (false, _) => TDCBF_OK_BUTTON,
+        },
+        pszWindowTitle: caption.as_ptr(),
+        u1: main_icon,
+        pszContent: text.as_ptr(),
+        pszExpandedInformation: details.as_ptr(),
+        pszFooter: footer.as_ref().map_or(null(), |footer| footer.as_ptr()),
+        pfCallback: Some(task_dialog_callback),
+        ..zeroed()
+    };
+
+    let mut button = 0;
+    let hr = function(&config, &mut button, null_mut(), null_mut());
+    FreeLibrary(comctl32);
+
+    Some(match SUCCEEDED(hr) {
+        true => Ok(button),
+        false => Err(std::io::Error::from_raw_os_error(hr).into()),
+    })
+}
+
+unsafe extern "system" fn task_dialog_callback(
+    _hwnd: HWND,
+    msg: UINT,
+    _wparam: WPARAM,
+    _lparam: LPARAM,
+    _data: LONG_PTR,
+) -> HRESULT {
+    use std::ptr::null_mut;
+    use winapi::shared::winerror::S_OK;
+    use winapi::um::commctrl::TDN_HYPERLINK_CLICKED;
+
+    if msg == TDN_HYPERLINK_CLICKED {
+        open_help_link(null_mut());
+    }
+
+    S_OK
+}
+
+struct BoxHook {
+    labels: Vec<(i32, String)>,
+    countdown: Option<Countdown>,
+    monitor: Option<usize>,
+    no_activate: bool,
+    started: bool,
+}
+
+struct Countdown {
+    button: i32,
+    label: String,
+    remaining: u64,
+}
+
+thread_local! {
+    static HOOK: RefCell<Option<BoxHook>> = const { RefCell::new(None) };
+    static HELP_URL: RefCell<Option<String>> = const { RefCell::new(None) };
+    static ESCAPE_ANSWERS_NO: Cell<bool> = const { Cell::new(false) };
+    static WITHOUT_CLICK: Cell<bool> = const { Cell::new(false) };
+}
+
+const COUNTDOWN_TIMER: UINT_PTR = 1;
+
+unsafe extern "system" fn message_box_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    use std::ptr::null_mut;
+    use winapi::um::winuser::{
+        CallNextHookEx, GetDlgItem, GetWindowTextLengthW, GetWindowTextW, SetDlgItemTextW,
+        SetTimer, HCBT_ACTIVATE,
+    };
+
+    if code == HCBT_ACTIVATE {
+        let hwnd = wparam as HWND;
+
+        let (start, no_activate) = HOOK.with(|hook| match hook.borrow_mut().as_mut() {
+            Some(hook) if !hook.started => {
+                hook.started = true;
+
+                for (button, label) in &hook.labels {
+                    SetDlgItemTextW(hwnd, *button, to_wide(label).as_ptr());
+                }
+
+                if let Some(index) = hook.monitor {
+                    super::center_on_monitor(hwnd, index);
+                }
+
+                let start = match hook.countdown.as_mut() {
+                    Some(countdown) => {
+                        let button = GetDlgItem(hwnd, countdown.button);
+                        let mut buf = vec![0u16; GetWindowTextLengthW(button) as usize + 1];
+                        let len = GetWindowTextW(button, buf.as_mut_ptr(), buf.len() as i32);
+                        countdown.label = String::from_utf16_lossy(&buf[..len as usize]);
+                        true
+                    }
+                    None => false,
+                };
+                (start, hook.no_activate)
+            }
+            _ => (false, false),
+        });
+
+        if start {
+            update_countdown(hwnd);
+            SetTimer(hwnd, COUNTDOWN_TIMER, 1000, Some(countdown_tick));
+        }
+
+        // Nonzero keeps the window from being activated. Only the first activation is held
+        // back, so the message box still takes the focus once it's clicked.
+        if no_activate {
+            return 1;
+        }
+    }
+
+    CallNextHookEx(null_mut(), code, wparam, lparam)
+}
+
+// There's nowhere to report a failure from here, so the message box just stays open
+unsafe extern "system" fn open_help_link(_info: LPHELPINFO) {
+    if let Some(url) = HELP_URL.with(|url| url.borrow().clone()) {
+        let _ = crate::open_path(Path::new(&url));
+    }
+}
+
+unsafe extern "system" fn key_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    use std::ptr::null_mut;
+    use winapi::um::winuser::{
+        CallNextHookEx, GetActiveWindow, PostMessageW, HC_ACTION, IDNO, VK_ESCAPE, VK_RETURN,
+        VK_SPACE, WM_COMMAND,
+    };
+
+    // The top bit of `lparam` is set when the key is being released
+    let pressed = lparam as u32 & 0x8000_0000 == 0;
+
+    if code == HC_ACTION && pressed {
+        match wparam as i32 {
+            // A message box without a Cancel button ignores Escape, so it's turned into "No" here
+            VK_ESCAPE if ESCAPE_ANSWERS_NO.with(Cell::get) => {
+                let hwnd = GetActiveWindow();
+                if !hwnd.is_null() {
+                    PostMessageW(hwnd, WM_COMMAND, IDNO as WPARAM, 0);
+                    return 1;
+                }
+            }
+            // Either key presses the focused button, which starts out as the default one
+            VK_RETURN | VK_SPACE => WITHOUT_CLICK.with(|without_click| without_click.set(true)),
+            _ => {}
+        }
+    }
+
+    CallNextHookEx(null_mut(), code, wparam, lparam)
+}
+
+unsafe extern "system" fn countdown_tick(hwnd: HWND, _msg: UINT, id: UINT_PTR, _time: DWORD) {
+    use winapi::um::winuser::{KillTimer, PostMessageW, WM_COMMAND};
+
+    let finished = HOOK.with(|hook| match hook.borrow_mut().as_mut() {
+        Some(BoxHook {
+            countdown: Some(countdown),
+            ..
+        }) => {
+            countdown.remaining = countdown.remaining.saturating_sub(1);
+            match countdown.remaining {
+                0 => Some(countdown.button),
+                _ => None,
+            }
+        }
+        _ => None,
+    });
+
+    match finished {
+        Some(button) => {
+            WITHOUT_CLICK.with(|without_click| without_click.set(true));
+            KillTimer(hwnd, id);
+            PostMessageW(hwnd, WM_COMMAND, button as WPARAM, 0);
+        }
+        None => update_countdown(hwnd),
+    }
+}
+
+unsafe fn update_countdown(hwnd: HWND) {
+    use winapi::um::winuser::SetDlgItemTextW;
+
+    HOOK.with(|hook| {
+        if let Some(BoxHook {
+            countdown: Some(countdown),
+            ..
+        }) = &*hook.borrow()
+        {
+            let label = to_wide(format!("{} ({})", countdown.label, countdown.remaining));
+            SetDlgItemTextW(hwnd, countdown.button, label.as_ptr());
+        }
+    });
 }
@@ -1,15 +1,147 @@
-mod file;
+pub(crate) mod file;
+mod file_dialog;
+mod font;
+pub(crate) mod input;
 mod message;
+mod printer;
+pub(crate) mod shell;
 
+use std::sync::Mutex;
+
+// Characters outside the BMP, like most emoji, come out as surrogate pairs, which is what the W
+// functions take. Whether they're drawn in color or at all is up to the font of the control.
+fn to_wide(s: impl AsRef<std::ffi::OsStr>) -> Vec<u16> {
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+
+    s.as_ref().encode_wide().chain(once(0)).collect()
+}
+
+// Moves the window to the middle of the work area of the `index`th monitor, in the order
+// Windows enumerates them. An index past the last monitor leaves the window where it is.
+unsafe fn center_on_monitor(hwnd: winapi::shared::windef::HWND, index: usize) {
+    use std::mem::{size_of, zeroed};
+    use std::ptr::{null, null_mut};
+    use winapi::shared::minwindef::{BOOL, LPARAM, TRUE};
+    use winapi::shared::windef::{HDC, HMONITOR, LPRECT, RECT};
+    use winapi::um::winuser::{
+        EnumDisplayMonitors, GetMonitorInfoW, GetWindowRect, SetWindowPos, MONITORINFO,
+        SWP_NOACTIVATE, SWP_NOSIZE, SWP_NOZORDER,
+    };
+
+    unsafe extern "system" fn collect(monitor: HMONITOR, _: HDC, _: LPRECT, data: LPARAM) -> BOOL {
+        (*(data as *mut Vec<HMONITOR>)).push(monitor);
+        TRUE
+    }
+
+    let mut monitors: Vec<HMONITOR> = vec![];
+    EnumDisplayMonitors(
+        null_mut(),
+        null(),
+        Some(collect),
+        &mut monitors as *mut Vec<HMONITOR> as LPARAM,
+    );
+
+    let monitor = match monitors.get(index) {
+        Some(monitor) => *monitor,
+        None => return,
+    };
+
+    let mut info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..zeroed()
+    };
+    let mut window: RECT = zeroed();
+    if GetMonitorInfoW(monitor, &mut info) == 0 || GetWindowRect(hwnd, &mut window) == 0 {
+        return;
+    }
+
+    let area = info.rcWork;
+    let x = area.left + (area.right - area.left - (window.right - window.left)) / 2;
+    let y = area.top + (area.bottom - area.top - (window.bottom - window.top)) / 2;
+
+    SetWindowPos(
+        hwnd,
+        null_mut(),
+        x,
+        y,
+        0,
+        0,
+        SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+    );
+}
+
+/// How the process tells Windows it handles high DPI screens. Anything but `Inherit` is applied
+/// once, right before the first dialog is shown, since a process can only set it one time. Host
+/// applications that set it themselves, in code or in their manifest, should leave it as `Inherit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DpiAwareness {
+    Inherit,
+    Unaware,
+    System,
+    PerMonitorV2,
+}
+
+// The `windows_dpi_awareness` feature predates the setting, and still makes `System` the default
+static DPI_AWARENESS: Mutex<DpiAwareness> =
+    Mutex::new(match cfg!(feature = "windows_dpi_awareness") {
+        true => DpiAwareness::System,
+        false => DpiAwareness::Inherit,
+    });
+
+/// Has no effect once a dialog has been shown.
+pub fn set_dpi_aware(awareness: DpiAwareness) {
+    *DPI_AWARENESS.lock().unwrap_or_else(|e| e.into_inner()) = awareness;
+}
+
+// Per-monitor awareness needs Windows 10 1703, so the function is looked up at runtime. Older
+// versions only get system awareness.
+unsafe fn apply_dpi_awareness(awareness: DpiAwareness) {
+    use winapi::shared::minwindef::BOOL;
+    use winapi::shared::windef::{
+        DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        DPI_AWARENESS_CONTEXT_SYSTEM_AWARE, DPI_AWARENESS_CONTEXT_UNAWARE,
+    };
+    use winapi::um::libloaderapi::{GetModuleHandleW, GetProcAddress};
+    use winapi::um::winuser::SetProcessDPIAware;
+
+    type SetProcessDpiAwarenessContext = unsafe extern "system" fn(DPI_AWARENESS_CONTEXT) -> BOOL;
+
+    let context = match awareness {
+        DpiAwareness::Inherit => return,
+        DpiAwareness::Unaware => DPI_AWARENESS_CONTEXT_UNAWARE,
+        DpiAwareness::System => DPI_AWARENESS_CONTEXT_SYSTEM_AWARE,
+        DpiAwareness::PerMonitorV2 => DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+    };
+
+    let user32 = GetModuleHandleW(to_wide("user32.dll").as_ptr());
+    let function = GetProcAddress(user32, b"SetProcessDpiAwarenessContext\0".as_ptr() as _);
+    if !function.is_null() {
+        let function: SetProcessDpiAwarenessContext = std::mem::transmute(function);
+        if function(context) != 0 {
+            return;
+        }
+    }
+
+    if awareness != DpiAwareness::Unaware {
+        SetProcessDPIAware();
+    }
+}
+
+// Called at the start of every dialog, from whichever thread shows it, so it must stay cheap and
+// safe to call any number of times. Only process-wide setup that must happen exactly once goes
+// here. State with a narrower lifetime is paired up where it's used instead: COM is initialized
+// and uninitialized around each file dialog by `ComInit`, which COM itself counts per thread, and
+// the visual styles activation context is created once but activated only for the duration of
+// `with_visual_styles`.
 fn process_init() {
     use std::sync::Once;
 
     static INIT: Once = Once::new();
 
-    #[allow(unused_unsafe)]
-    INIT.call_once(|| unsafe {
-        #[cfg(feature = "windows_dpi_awareness")]
-        winapi::um::winuser::SetProcessDPIAware();
+    INIT.call_once(|| {
+        let awareness = *DPI_AWARENESS.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe { apply_dpi_awareness(awareness) };
     });
 }
 
@@ -25,17 +157,28 @@ fn with_visual_styles<T>(f: impl Fn() -> T) -> T {
     use winapi::um::handleapi::INVALID_HANDLE_VALUE;
     use winapi::um::winbase::{ActivateActCtx, DeactivateActCtx};
 
+    // Deactivates the context even if `f` panics, since an activation left on the thread's stack
+    // would apply to everything the thread does afterwards.
+    struct Activation(usize);
+
+    impl Drop for Activation {
+        fn drop(&mut self) {
+            unsafe { DeactivateActCtx(0, self.0) };
+        }
+    }
+
     let handle = act_ctx::get_handle();
+    if handle == INVALID_HANDLE_VALUE {
+        return f();
+    }
 
-    if handle != INVALID_HANDLE_VALUE {
-        let mut cookie = 0;
-        unsafe { ActivateActCtx(handle, &mut cookie) };
-        let result = f();
-        unsafe { DeactivateActCtx(0, cookie) };
-        result
-    } else {
-        f()
+    let mut cookie = 0;
+    if unsafe { ActivateActCtx(handle, &mut cookie) } == 0 {
+        return f();
     }
+
+    let _activation = Activation(cookie);
+    f()
 }
 
 #[cfg(feature = "windows_visual_styles")]
@@ -65,8 +208,8 @@ mod act_ctx {
             .chain(std::iter::once(0))
             .collect();
 
-        let mut context = ACTCTXW {
-            cbSize: std::mem::size_of::<ACTCTXW> as ULONG,
+        let context = ACTCTXW {
+            cbSize: std::mem::size_of::<ACTCTXW>() as ULONG,
             hModule: unsafe { GetModuleHandleW(module_name.as_mut_ptr()) },
             lpResourceName: MAKEINTRESOURCEW(124),
             dwFlags: ACTCTX_FLAG_HMODULE_VALID | ACTCTX_FLAG_RESOURCE_NAME_VALID,
@@ -74,7 +217,7 @@ mod act_ctx {
         };
 
         ActCtxHandle {
-            handle: unsafe { CreateActCtxW(&mut context) },
+            handle: unsafe { CreateActCtxW(&context) },
         }
     }
 
@@ -86,3 +229,25 @@ mod act_ctx {
         ACT_CTX_HANDLE.get_or_init(init).handle
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::to_wide;
+
+    #[test]
+    fn to_wide_keeps_surrogate_pairs() {
+        assert_eq!(to_wide("😀"), [0xd83d, 0xde00, 0]);
+    }
+
+    #[test]
+    fn to_wide_encodes_an_emoji_title() {
+        let title = to_wide("Saved 😀 OK");
+
+        assert_eq!(
+            String::from_utf16(&title[..title.len() - 1]).unwrap(),
+            "Saved 😀 OK"
+        );
+        assert_eq!(title.last(), Some(&0));
+        assert_eq!(title.iter().filter(|&&unit| unit == 0).count(), 1);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{r#impl, trace, Dialog, Error, PickPrinter, PrinterSelection, Result};
+use winapi::shared::minwindef::HGLOBAL;
+
+impl Dialog for PickPrinter {
+    type Output = Option<PrinterSelection>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        trace::dialog("PickPrinter", None, || {
+            r#impl::backend("PickPrinter", || {
+                super::process_init();
+
+                choose_printer()
+            })
+        })
+    }
+}
+
+// The Print Setup dialog picks a printer without asking about pages or copies
+fn choose_printer() -> Result<Option<PrinterSelection>> {
+    use std::mem::{size_of, zeroed};
+    use winapi::um::commdlg::{CommDlgExtendedError, PrintDlgW, PD_PRINTSETUP, PRINTDLGW};
+    use winapi::um::winbase::GlobalFree;
+
+    let mut params = PRINTDLGW {
+        lStructSize: size_of::<PRINTDLGW>() as u32,
+        Flags: PD_PRINTSETUP,
+        ..unsafe { zeroed() }
+    };
+    let params_ptr = &mut params as *mut PRINTDLGW;
+
+    let ret = super::with_visual_styles(|| unsafe { PrintDlgW(params_ptr) });
+
+    // A zero return is a cancel unless the dialog left an error code behind, like the one for
+    // there being no printers at all
+    if ret == 0 {
+        return match unsafe { CommDlgExtendedError() } {
+            0 => Ok(None),
+            code => Err(Error::ImplementationError(format!(
+                "PrintDlgW failed with {}",
+                code
+            ))),
+        };
+    }
+
+    // Both blocks are allocated by the dialog and left for the caller to free
+    let selection = unsafe { printer_selection(params.hDevNames) };
+    unsafe {
+        GlobalFree(params.hDevMode);
+        GlobalFree(params.hDevNames);
+    }
+
+    selection.map(Some)
+}
+
+// DEVNAMES is followed by its strings, with offsets counted in characters from its start.
+unsafe fn printer_selection(dev_names: HGLOBAL) -> Result<PrinterSelection> {
+    use winapi::um::commdlg::{DEVNAMES, DN_DEFAULTPRN};
+    use winapi::um::winbase::{GlobalLock, GlobalUnlock};
+
+    let names = GlobalLock(dev_names) as *const DEVNAMES;
+    if names.is_null() {
+        return Err(Error::UnexpectedOutput("PrintDlgW"));
+    }
+
+    let device = (names as *const u16).add((*names).wDeviceOffset as usize);
+    let len = (0..).take_while(|&i| *device.add(i) != 0).count();
+    let selection = PrinterSelection {
+        name: String::from_utf16_lossy(std::slice::from_raw_parts(device, len)),
+        is_default: (*names).wDefault & DN_DEFAULTPRN != 0,
+    };
+
+    GlobalUnlock(dev_names);
+    Ok(selection)
+}
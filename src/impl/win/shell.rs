@@ -0,0 +1,49 @@
+use super::to_wide;
+use crate::{Error, Result};
+use std::os::windows::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+
+pub(crate) fn reveal(path: &Path) -> Result<()> {
+    // explorer only understands the path when it's quoted right after the comma, which the
+    // regular argument quoting doesn't produce. Its exit code doesn't mean anything either.
+    Command::new("explorer")
+        .raw_arg(format!("/select,\"{}\"", path.display()))
+        .spawn()?;
+
+    Ok(())
+}
+
+pub(crate) fn open(path: &Path) -> Result<()> {
+    use std::ptr::{null, null_mut};
+    use winapi::shared::winerror::{ERROR_FILE_NOT_FOUND, ERROR_PATH_NOT_FOUND};
+    use winapi::um::shellapi::{ShellExecuteW, SE_ERR_ASSOCINCOMPLETE, SE_ERR_NOASSOC};
+    use winapi::um::winuser::SW_SHOWNORMAL;
+
+    let operation = to_wide("open");
+    let file = to_wide(path.as_os_str());
+
+    let ret = unsafe {
+        ShellExecuteW(
+            null_mut(),
+            operation.as_ptr(),
+            file.as_ptr(),
+            null(),
+            null(),
+            SW_SHOWNORMAL,
+        )
+    } as isize;
+
+    // Anything above 32 means success, anything else is one of a handful of error codes
+    match ret as u32 {
+        _ if ret > 32 => Ok(()),
+        SE_ERR_NOASSOC | SE_ERR_ASSOCINCOMPLETE => Err(Error::NoHandler),
+        code @ (ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND) => {
+            Err(std::io::Error::from_raw_os_error(code as i32))?
+        }
+        code => Err(Error::ImplementationError(format!(
+            "ShellExecuteW failed with {}",
+            code
+        ))),
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{Dialog, LabelSet, MessageAlert, MessageType, Result};
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct InputText<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub default: &'a str,
+    pub labels: LabelSet<'a>,
+    pub monitor: Option<usize>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
+}
+
+impl<'a> InputText<'a> {
+    pub fn locale_labels(mut self, labels: LabelSet<'a>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Same as `MessageAlert::monitor`, only Windows places the dialog itself.
+    pub fn monitor(mut self, index: usize) -> Self {
+        self.monitor = Some(index);
+        self
+    }
+
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+
+    pub fn validate<F>(self, validator: F) -> ValidatedInput<'a, F>
+    where
+        F: FnMut(&str) -> std::result::Result<(), String>,
+    {
+        ValidatedInput {
+            input: self,
+            validator: RefCell::new(validator),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ConfirmByTyping<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub required_phrase: &'a str,
+    pub timeout: Option<Duration>,
+}
+
+/// Asks for a number until the answer parses. Answers outside `min` and `max` are turned down, or
+/// moved to the nearest bound when `clamp` is set.
+#[derive(Default)]
+pub struct InputNumber<'a, T> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub default: Option<T>,
+    pub min: Option<T>,
+    pub max: Option<T>,
+    pub clamp: bool,
+    pub labels: LabelSet<'a>,
+    pub timeout: Option<Duration>,
+}
+
+/// Asks for several values at once, such as a user name and a server. Each field is a label and a
+/// default value, and the values come back in the same order as the fields.
+#[derive(Default)]
+pub struct Form<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub fields: Vec<(&'a str, &'a str)>,
+    pub labels: LabelSet<'a>,
+    pub join_all_spaces: Option<bool>,
+    pub timeout: Option<Duration>,
+}
+
+impl<'a> Form<'a> {
+    pub fn add_field(mut self, label: &'a str, default: &'a str) -> Self {
+        self.fields.push((label, default));
+        self
+    }
+
+    /// Same as `TextInfo::join_all_spaces`.
+    pub fn join_all_spaces(mut self, join: bool) -> Self {
+        self.join_all_spaces = Some(join);
+        self
+    }
+}
+
+/// `show_ref` only borrows the dialog, while the validator may need to mutate its state
+pub struct ValidatedInput<'a, F> {
+    input: InputText<'a>,
+    validator: RefCell<F>,
+}
+
+impl<F> Dialog for ValidatedInput<'_, F>
+where
+    F: FnMut(&str) -> std::result::Result<(), String>,
+{
+    type Output = Option<String>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        let mut value = self.input.default.to_string();
+
+        loop {
+            let dialog = InputText {
+                title: self.input.title,
+                text: self.input.text,
+                default: &value,
+                labels: self.input.labels,
+                monitor: self.input.monitor,
+                retry: self.input.retry,
+                timeout: self.input.timeout,
+            };
+
+            let answer = match dialog.show()? {
+                Some(answer) => answer,
+                None => return Ok(None),
+            };
+
+            match (self.validator.borrow_mut())(&answer) {
+                Ok(()) => return Ok(Some(answer)),
+                Err(message) => {
+                    let dialog = MessageAlert {
+                        title: Some(self.input.title),
+                        text: &message,
+                        typ: MessageType::Error,
+                        labels: self.input.labels,
+                        monitor: self.input.monitor,
+                        retry: self.input.retry,
+                        timeout: self.input.timeout,
+                        ..Default::default()
+                    };
+                    dialog.show()?;
+
+                    value = answer;
+                }
+            }
+        }
+    }
+}
+
+impl Dialog for ConfirmByTyping<'_> {
+    type Output = bool;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        let dialog = InputText {
+            title: self.title,
+            text: self.text,
+            timeout: self.timeout,
+            ..Default::default()
+        };
+
+        let answer = dialog
+            .validate(|answer| match answer == self.required_phrase {
+                true => Ok(()),
+                false => Err(format!("Type \"{}\" to confirm.", self.required_phrase)),
+            })
+            .show()?;
+
+        Ok(answer.is_some())
+    }
+}
+
+impl<T> Dialog for InputNumber<'_, T>
+where
+    T: FromStr + PartialOrd + Display + Clone,
+{
+    type Output = Option<T>;
+
+    fn show_ref(&self) -> Result<Self::Output> {
+        let default = match &self.default {
+            Some(default) => default.to_string(),
+            None => String::new(),
+        };
+
+        let dialog = InputText {
+            title: self.title,
+            text: self.text,
+            default: &default,
+            labels: self.labels,
+            timeout: self.timeout,
+            ..Default::default()
+        };
+
+        let mut number = None;
+        let answer = dialog
+            .validate(|answer| {
+                number = Some(self.parse(answer)?);
+                Ok(())
+            })
+            .show()?;
+
+        Ok(answer.and(number))
+    }
+}
+
+impl<T> InputNumber<'_, T>
+where
+    T: FromStr + PartialOrd + Display + Clone,
+{
+    fn parse(&self, answer: &str) -> std::result::Result<T, String> {
+        let answer = answer.trim();
+        let number: T = answer
+            .parse()
+            .map_err(|_| format!("\"{}\" is not a number.", answer))?;
+
+        match (&self.min, &self.max) {
+            (Some(min), _) if number < *min => match self.clamp {
+                true => Ok(min.clone()),
+                false => Err(format!("The number can't be less than {}.", min)),
+            },
+            (_, Some(max)) if number > *max => match self.clamp {
+                true => Ok(max.clone()),
+                false => Err(format!("The number can't be more than {}.", max)),
+            },
+            _ => Ok(number),
+        }
+    }
+}
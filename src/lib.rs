@@ -1,3 +1,8 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,11 +16,66 @@ pub enum Error {
     #[error("failed to parse the string returned from implementation")]
     UnexpectedOutput(&'static str),
 
-    #[error("cannot find any dialog implementation (kdialog/zanity)")]
+    #[error("cannot find any dialog implementation (kdialog/zenity/xdg-open)")]
     NoImplementation,
 
+    /// The backend is there, but has no way to show this kind of dialog
+    #[error("the {backend:?} backend cannot show {dialog}")]
+    Unsupported {
+        dialog: &'static str,
+        backend: Backend,
+    },
+
     #[error("the implementation reports error")]
     ImplementationError(String),
+
+    #[error("more files are selected than allowed")]
+    TooManySelected(usize),
+
+    #[error("the file is larger than allowed")]
+    FileTooLarge(u64),
+
+    #[error("the default filter is not one of the filters")]
+    InvalidFilterIndex(usize),
+
+    /// Holds the malformed extension, or the name of the filter without any extensions
+    #[error("a file filter is empty or malformed")]
+    InvalidFilter(String),
+
+    #[error("there is no application to open the path with")]
+    NoHandler,
+
+    #[error("the location url does not refer to a local file")]
+    UnsupportedUrl(String),
+
+    #[error("the dialog was closed after not being answered within {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("the {backend:?} backend is installed but failed to execute")]
+    BackendSpawnFailed {
+        backend: Backend,
+        source: std::io::Error,
+    },
+}
+
+impl Error {
+    /// Failures that come from the backend itself rather than from the dialog's settings, and that
+    /// may well not happen on another try.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::ImplementationError(_) | Error::BackendSpawnFailed { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    KDialog,
+    Zenity,
+    XdgOpen,
+    OsaScript,
+    Win32,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -23,7 +83,378 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub trait Dialog {
     type Output;
 
-    fn show(self) -> Result<Self::Output>;
+    fn show(self) -> Result<Self::Output>
+    where
+        Self: Sized,
+    {
+        self.show_ref()
+    }
+
+    /// Shows the dialog without consuming it, so the same dialog can be shown again.
+    fn show_ref(&self) -> Result<Self::Output>;
+
+    /// Shows the dialog and passes its output through `f`.
+    fn show_map<U>(self, f: impl FnOnce(Self::Output) -> U) -> Result<U>
+    where
+        Self: Sized,
+    {
+        self.show().map(f)
+    }
+
+    /// Shows the dialog on a worker thread and hands the result to `callback` on that thread.
+    fn on_complete<F>(self, callback: F)
+    where
+        Self: Sized + Send + 'static,
+        Self::Output: Send,
+        F: FnOnce(Result<Self::Output>) + Send + 'static,
+    {
+        std::thread::spawn(move || callback(self.show()));
+    }
+
+    /// Shows the dialog on a worker thread and returns the thread's handle, which `join`s to the
+    /// result.
+    fn spawn_thread(self) -> std::thread::JoinHandle<Result<Self::Output>>
+    where
+        Self: Sized + Send + 'static,
+        Self::Output: Send + 'static,
+    {
+        std::thread::spawn(move || self.show())
+    }
+}
+
+/// `Dialog` isn't object safe, so dialogs with different outputs can't share a `Vec`. Boxing them
+/// as `ErasedDialog` makes them share `DialogValue` as their output instead.
+pub trait ErasedDialog {
+    fn run(self: Box<Self>) -> Result<DialogValue>;
+}
+
+impl<D> ErasedDialog for D
+where
+    D: Dialog,
+    D::Output: Into<DialogValue>,
+{
+    fn run(self: Box<Self>) -> Result<DialogValue> {
+        (*self).show().map(Into::into)
+    }
+}
+
+pub type BoxedDialog<'a> = Box<dyn ErasedDialog + 'a>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DialogValue {
+    Unit,
+    Bool(bool),
+    Path(Option<PathBuf>),
+    Paths(Vec<PathBuf>),
+    Item(Option<(PathBuf, ItemKind)>),
+    Text(Option<String>),
+    SaveChanges(SaveChangesChoice),
+}
+
+impl From<()> for DialogValue {
+    fn from(_: ()) -> Self {
+        DialogValue::Unit
+    }
+}
+
+impl From<bool> for DialogValue {
+    fn from(value: bool) -> Self {
+        DialogValue::Bool(value)
+    }
+}
+
+impl From<Option<PathBuf>> for DialogValue {
+    fn from(value: Option<PathBuf>) -> Self {
+        DialogValue::Path(value)
+    }
+}
+
+impl From<Vec<PathBuf>> for DialogValue {
+    fn from(value: Vec<PathBuf>) -> Self {
+        DialogValue::Paths(value)
+    }
+}
+
+impl From<Option<(PathBuf, ItemKind)>> for DialogValue {
+    fn from(value: Option<(PathBuf, ItemKind)>) -> Self {
+        DialogValue::Item(value)
+    }
+}
+
+impl From<Option<String>> for DialogValue {
+    fn from(value: Option<String>) -> Self {
+        DialogValue::Text(value)
+    }
+}
+
+impl From<SaveChangesChoice> for DialogValue {
+    fn from(value: SaveChangesChoice) -> Self {
+        DialogValue::SaveChanges(value)
+    }
+}
+
+/// Whether dialogs can be shown at all right now, so command line tools can fall back to the
+/// terminal up front instead of waiting for `Error::NoImplementation`. On Linux that takes a
+/// display and either zenity or kdialog. With the `mock` feature, dialogs never need either.
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+pub fn can_show_gui() -> bool {
+    r#impl::gnu::can_show_gui()
+}
+
+/// Whether dialogs can be shown at all right now. Only Linux can be without a way to show them, and
+/// with the `mock` feature they always can.
+#[cfg(any(not(target_os = "linux"), feature = "mock"))]
+pub fn can_show_gui() -> bool {
+    true
+}
+
+/// Alerts shown from several threads at once all open at the same time, stacked on top of each
+/// other. Turned on, each `MessageAlert` instead waits for the ones shown before it to be closed,
+/// so a burst of errors comes up one at a time, in the order they were shown. Other dialogs
+/// aren't held up. Off by default.
+pub fn set_serialize_dialogs(serialize: bool) {
+    SERIALIZE_DIALOGS.store(serialize, Ordering::Relaxed);
+}
+
+static SERIALIZE_DIALOGS: AtomicBool = AtomicBool::new(false);
+
+// Alerts take a ticket and wait until it's their turn. Unlike holding a mutex, that keeps them in
+// the order they were shown.
+struct AlertQueue {
+    next: u64,
+    showing: u64,
+}
+
+static ALERT_QUEUE: Mutex<AlertQueue> = Mutex::new(AlertQueue {
+    next: 0,
+    showing: 0,
+});
+static ALERT_DONE: Condvar = Condvar::new();
+
+pub(crate) fn in_alert_queue<T>(show: impl FnOnce() -> T) -> T {
+    // Passes the turn on even when showing the alert panics
+    struct Turn;
+
+    impl Drop for Turn {
+        fn drop(&mut self) {
+            let mut queue = ALERT_QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+            queue.showing += 1;
+            ALERT_DONE.notify_all();
+        }
+    }
+
+    if !SERIALIZE_DIALOGS.load(Ordering::Relaxed) {
+        return show();
+    }
+
+    let mut queue = ALERT_QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    let ticket = queue.next;
+    queue.next += 1;
+    while queue.showing != ticket {
+        queue = ALERT_DONE.wait(queue).unwrap_or_else(|e| e.into_inner());
+    }
+    drop(queue);
+
+    let _turn = Turn;
+    show()
+}
+
+/// A dialog described by data alone, for plugins and scripts that can't name the dialog types at
+/// compile time. Only the main settings of each dialog are covered, and each variant owns them, so
+/// titles and filter names that come from a translation system at runtime can be moved in as they
+/// are. `filters` are named groups, like `add_filter`, and come after the unnamed `filter`.
+///
+/// With the `serde` feature, this is the type to read dialogs from config files with. The dialog
+/// types themselves borrow their text, which couldn't hold strings with escapes in them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DialogSpec {
+    MessageAlert {
+        title: String,
+        text: String,
+        typ: MessageType,
+    },
+    MessageConfirm {
+        title: String,
+        text: String,
+        typ: MessageType,
+    },
+    InputText {
+        title: String,
+        text: String,
+        default: String,
+    },
+    ConfirmByTyping {
+        title: String,
+        text: String,
+        required_phrase: String,
+    },
+    TextInfo {
+        title: String,
+        content: String,
+    },
+    AskSaveChanges {
+        document_name: String,
+    },
+    OpenSingleFile {
+        dir: Option<String>,
+        filter: Option<Vec<String>>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        filters: Vec<(String, Vec<String>)>,
+    },
+    OpenMultipleFile {
+        dir: Option<String>,
+        filter: Option<Vec<String>>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        filters: Vec<(String, Vec<String>)>,
+    },
+    OpenSingleDir {
+        dir: Option<String>,
+    },
+    OpenSingleItem {
+        dir: Option<String>,
+        filter: Option<Vec<String>>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        filters: Vec<(String, Vec<String>)>,
+    },
+    SaveFile {
+        dir: Option<String>,
+        name: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        filters: Vec<(String, Vec<String>)>,
+    },
+}
+
+impl DialogSpec {
+    pub fn run(self) -> Result<DialogValue> {
+        fn borrow_filter(filter: &Option<Vec<String>>) -> Option<Vec<&str>> {
+            filter
+                .as_ref()
+                .map(|filter| filter.iter().map(String::as_str).collect())
+        }
+
+        fn borrow_filters(filters: &[(String, Vec<String>)]) -> Vec<(&str, Vec<&str>)> {
+            filters
+                .iter()
+                .map(|(name, extensions)| {
+                    (
+                        name.as_str(),
+                        extensions.iter().map(String::as_str).collect(),
+                    )
+                })
+                .collect()
+        }
+
+        fn file_filters<'a>(groups: &'a [(&'a str, Vec<&'a str>)]) -> Vec<FileFilter<'a>> {
+            groups
+                .iter()
+                .map(|(name, extensions)| FileFilter { name, extensions })
+                .collect()
+        }
+
+        match self {
+            DialogSpec::MessageAlert { title, text, typ } => MessageAlert {
+                title: Some(&title),
+                text: &text,
+                typ,
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::MessageConfirm { title, text, typ } => MessageConfirm {
+                title: Some(&title),
+                text: &text,
+                typ,
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::InputText {
+                title,
+                text,
+                default,
+            } => InputText {
+                title: &title,
+                text: &text,
+                default: &default,
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::ConfirmByTyping {
+                title,
+                text,
+                required_phrase,
+            } => ConfirmByTyping {
+                title: &title,
+                text: &text,
+                required_phrase: &required_phrase,
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::TextInfo { title, content } => TextInfo {
+                title: &title,
+                content,
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::AskSaveChanges { document_name } => {
+                ask_save_changes(&document_name).map(Into::into)
+            }
+            DialogSpec::OpenSingleFile {
+                dir,
+                filter,
+                filters,
+            } => OpenSingleFile {
+                dir: dir.as_deref(),
+                filter: borrow_filter(&filter).as_deref(),
+                filters: file_filters(&borrow_filters(&filters)),
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::OpenMultipleFile {
+                dir,
+                filter,
+                filters,
+            } => OpenMultipleFile {
+                dir: dir.as_deref(),
+                filter: borrow_filter(&filter).as_deref(),
+                filters: file_filters(&borrow_filters(&filters)),
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::OpenSingleDir { dir } => OpenSingleDir {
+                dir: dir.as_deref(),
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::OpenSingleItem {
+                dir,
+                filter,
+                filters,
+            } => OpenSingleItem {
+                dir: dir.as_deref(),
+                filter: borrow_filter(&filter).as_deref(),
+                filters: file_filters(&borrow_filters(&filters)),
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+            DialogSpec::SaveFile { dir, name, filters } => SaveFile {
+                dir: dir.as_deref(),
+                name: &name,
+                filters: file_filters(&borrow_filters(&filters)),
+                ..Default::default()
+            }
+            .show()
+            .map(Into::into),
+        }
+    }
 }
 
 mod message;
@@ -32,4 +463,33 @@ pub use message::*;
 mod file;
 pub use file::*;
 
+mod input;
+pub use input::*;
+
+mod font;
+pub use font::*;
+
+mod printer;
+pub use printer::*;
+
+mod native;
+pub use native::*;
+
+mod hook;
+pub use hook::*;
+
+mod shell;
+pub use shell::*;
+
+#[cfg(feature = "mock")]
+mod mock;
+#[cfg(feature = "mock")]
+pub use mock::*;
+
 mod r#impl;
+#[cfg(target_os = "linux")]
+pub use r#impl::gnu::set_backend_path;
+#[cfg(target_os = "windows")]
+pub use r#impl::win::{set_dpi_aware, DpiAwareness};
+
+mod trace;
@@ -1,17 +1,489 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MessageType {
+    #[default]
     Info,
     Warning,
     Error,
+    None,
 }
 
+/// Left without a `title`, the dialog is titled the platform's own way: Windows uses the
+/// application's name, macOS leaves the title out, and zenity and kdialog fall back to theirs.
+#[derive(Default)]
 pub struct MessageAlert<'a> {
-    pub title: &'a str,
+    pub title: Option<&'a str>,
     pub text: &'a str,
     pub typ: MessageType,
+    pub markup: bool,
+    pub labels: LabelSet<'a>,
+    pub help_link: Option<(&'a str, &'a str)>,
+    pub details: Option<&'a str>,
+    pub announcement: Option<&'a str>,
+    pub silent: bool,
+    pub no_activate: bool,
+    pub wrap_at: Option<usize>,
+    pub monitor: Option<usize>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
 }
 
+#[derive(Default)]
 pub struct MessageConfirm<'a> {
-    pub title: &'a str,
+    pub title: Option<&'a str>,
     pub text: &'a str,
     pub typ: MessageType,
+    pub markup: bool,
+    pub labels: LabelSet<'a>,
+    pub help_link: Option<(&'a str, &'a str)>,
+    pub details: Option<&'a str>,
+    pub countdown: Option<(Duration, bool)>,
+    pub ignore_escape: bool,
+    pub announcement: Option<&'a str>,
+    pub silent: bool,
+    pub no_activate: bool,
+    pub wrap_at: Option<usize>,
+    pub monitor: Option<usize>,
+    pub retry: Option<(u32, Duration)>,
+    pub timeout: Option<Duration>,
+}
+
+/// How a confirmation was answered. `via_default` is whether the answer came without a click,
+/// from the keyboard or the countdown running out. Only Windows watches for that; elsewhere it's
+/// `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConfirmDetail {
+    pub answer: bool,
+    pub via_default: Option<bool>,
+}
+
+/// Button labels for apps that ship their own translations. Labels that aren't set keep the
+/// system's text, and each dialog only uses the ones for the buttons it has.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LabelSet<'a> {
+    pub ok: Option<&'a str>,
+    pub cancel: Option<&'a str>,
+    pub yes: Option<&'a str>,
+    pub no: Option<&'a str>,
+}
+
+impl<'a> MessageAlert<'a> {
+    pub fn locale_labels(mut self, labels: LabelSet<'a>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Adds a link to `url`. Windows and macOS show it as an extra button, while zenity and kdialog
+    /// put it under the text. Following it leaves the dialog waiting for an answer.
+    pub fn help_link(mut self, label: &'a str, url: &'a str) -> Self {
+        self.help_link = Some((label, url));
+        self
+    }
+
+    /// For error reports, a stack trace or error chain that stays out of sight until the user asks
+    /// for it. Windows shows a task dialog that expands to reveal it. Task dialogs need version 6
+    /// of the common controls, which only processes with a manifest asking for it or the
+    /// `windows_visual_styles` feature have, so otherwise the details go under the text. macOS and
+    /// zenity add a "Show Details" button that shows them in a text box, then brings the message
+    /// back. kdialog shows alerts as detailed errors or warnings, and puts the details of a
+    /// confirmation under its text. On macOS, a confirmation that also has a help link has no room
+    /// left for the button, so they go under the text there too.
+    pub fn expandable_details(mut self, details: &'a str) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Every backend's dialog is read out by screen readers as it opens, title and text included.
+    /// This has them say `text` on top of that, for context the dialog itself doesn't give. Only
+    /// macOS can post an announcement, which VoiceOver speaks as the dialog appears. Windows
+    /// message boxes and zenity and kdialog have no way to, so there it does nothing.
+    pub fn accessibility_announcement(mut self, text: &'a str) -> Self {
+        self.announcement = Some(text);
+        self
+    }
+
+    /// Windows plays the system sound that goes with the icon when a message box opens. Silent
+    /// message boxes show the same icon without the sound. The other backends never play one.
+    pub fn silent(mut self, silent: bool) -> Self {
+        self.silent = silent;
+        self
+    }
+
+    /// For tray apps and daemons, shows the dialog without taking the focus from whatever the
+    /// user is doing. Only Windows can hold back the activation; it takes effect once the user
+    /// clicks the message box. `display dialog` on macOS and zenity and kdialog always come up
+    /// active, if the window manager lets them, so there it does nothing.
+    pub fn no_activate(mut self, no_activate: bool) -> Self {
+        self.no_activate = no_activate;
+        self
+    }
+
+    /// Breaks lines longer than `column` characters before the text reaches the backend. Text is
+    /// left as it is unless this is set.
+    pub fn wrap_at(mut self, column: usize) -> Self {
+        self.wrap_at = Some(column);
+        self
+    }
+
+    /// Centers the dialog on the `index`th monitor, counting the way the system enumerates them.
+    /// Only Windows places the dialog itself; macOS, zenity and kdialog leave it to the system.
+    pub fn monitor(mut self, index: usize) -> Self {
+        self.monitor = Some(index);
+        self
+    }
+
+    /// Shows the dialog again, up to `count` more times and `backoff` apart, when zenity or kdialog
+    /// fails in a way that may not happen twice. Answers, cancelling included, are never retried.
+    /// The other backends call the system directly, so there it does nothing.
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+}
+
+impl<'a> MessageConfirm<'a> {
+    pub fn locale_labels(mut self, labels: LabelSet<'a>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn help_link(mut self, label: &'a str, url: &'a str) -> Self {
+        self.help_link = Some((label, url));
+        self
+    }
+
+    pub fn expandable_details(mut self, details: &'a str) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Answers with `default` once `duration` has passed. Windows counts down on the button that
+    /// will be chosen. macOS, zenity and kdialog can't change the dialog once it's shown, so they
+    /// just answer when the time is up.
+    pub fn countdown(mut self, duration: Duration, default: bool) -> Self {
+        self.countdown = Some((duration, default));
+        self
+    }
+
+    /// Escape answers "No" unless this is turned off. zenity and kdialog always treat Escape that
+    /// way, so turning it off only has an effect on Windows and macOS.
+    pub fn cancel_on_escape(mut self, cancel: bool) -> Self {
+        self.ignore_escape = !cancel;
+        self
+    }
+
+    pub fn accessibility_announcement(mut self, text: &'a str) -> Self {
+        self.announcement = Some(text);
+        self
+    }
+
+    pub fn silent(mut self, silent: bool) -> Self {
+        self.silent = silent;
+        self
+    }
+
+    pub fn no_activate(mut self, no_activate: bool) -> Self {
+        self.no_activate = no_activate;
+        self
+    }
+
+    pub fn wrap_at(mut self, column: usize) -> Self {
+        self.wrap_at = Some(column);
+        self
+    }
+
+    pub fn monitor(mut self, index: usize) -> Self {
+        self.monitor = Some(index);
+        self
+    }
+
+    pub fn retry(mut self, count: u32, backoff: Duration) -> Self {
+        self.retry = Some((count, backoff));
+        self
+    }
+}
+
+// Only the Windows backend can tell how the dialog was answered.
+#[cfg(not(target_os = "windows"))]
+impl MessageConfirm<'_> {
+    pub fn show_detailed(self) -> crate::Result<ConfirmDetail> {
+        use crate::Dialog;
+
+        Ok(ConfirmDetail {
+            answer: self.show()?,
+            via_default: None,
+        })
+    }
+}
+
+// The button macOS and zenity add for `expandable_details`
+#[cfg(not(target_os = "windows"))]
+pub(crate) const SHOW_DETAILS: &str = "Show Details";
+
+#[cfg(not(target_os = "windows"))]
+pub(crate) fn show_details(details: &str) -> crate::Result<()> {
+    use crate::Dialog;
+
+    TextInfo {
+        title: "Details",
+        content: details.to_string(),
+        join_all_spaces: None,
+    }
+    .show_ref()
+}
+
+/// Shows `content` in a read-only, scrollable text box, for logs and other text too long for a
+/// message. The content never goes on the backend's command line, so its size isn't limited by it.
+#[derive(Default)]
+pub struct TextInfo<'a> {
+    pub title: &'a str,
+    pub content: String,
+    pub join_all_spaces: Option<bool>,
+}
+
+impl TextInfo<'_> {
+    /// On macOS, `true` shows the dialog on every Space and `false` moves it to the active one,
+    /// rather than switching to the Space the application was last active on. Only the dialogs
+    /// built on an AppKit window take this: `TextInfo`, `Form` and `OpenSingleItem`. The others are
+    /// shown by osascript, which has no windows elsewhere to switch to. Other platforms ignore it.
+    pub fn join_all_spaces(mut self, join: bool) -> Self {
+        self.join_all_spaces = Some(join);
+        self
+    }
+}
+
+/// The "Save / Don't Save / Cancel" question asked before closing a document with unsaved
+/// changes. Each backend words it and orders its buttons the way the platform's own applications
+/// do, with "Save" as the default button.
+#[derive(Default)]
+pub struct AskSaveChanges<'a> {
+    pub document_name: &'a str,
+}
+
+// The first step of `OpenOrCreate`, with "Open…", "Create New…" and "Cancel" buttons. Gives
+// `Some(true)` for "Open…", `Some(false)` for "Create New…" and `None` when cancelled.
+pub(crate) struct AskOpenOrCreate<'a> {
+    pub title: Option<&'a str>,
+    pub text: &'a str,
+    /// Only zenity and kdialog run in a child process that can be timed out
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub timeout: Option<Duration>,
+}
+
+/// Closing the dialog without picking a button counts as `Cancel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SaveChangesChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+pub fn ask_save_changes(document_name: &str) -> crate::Result<SaveChangesChoice> {
+    use crate::Dialog;
+
+    AskSaveChanges { document_name }.show()
+}
+
+/// An indeterminate progress dialog for work of unknown length. Unlike the other dialogs, `show`
+/// returns as soon as the dialog is up, and it stays up until the returned handle is closed or
+/// dropped. It has no button, so the user can't close it.
+#[derive(Default)]
+pub struct Busy<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+}
+
+/// There's no window behind the handle with the `mock` feature, and changing its text or closing
+/// it does nothing then.
+pub struct BusyHandle {
+    pub(crate) window: Option<crate::r#impl::BusyWindow>,
+}
+
+impl BusyHandle {
+    pub fn set_text(&mut self, text: &str) -> crate::Result<()> {
+        match &mut self.window {
+            Some(window) => window.set_text(text),
+            None => Ok(()),
+        }
+    }
+
+    /// Dropping the handle closes the dialog too, but ignores any error doing so.
+    pub fn close(mut self) -> crate::Result<()> {
+        match self.window.take() {
+            Some(window) => window.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for BusyHandle {
+    fn drop(&mut self) {
+        if let Some(window) = self.window.take() {
+            let _ = window.close();
+        }
+    }
+}
+
+/// Whether dialogs of type `typ` show their icon. Minimal Linux icon themes can lack the icons
+/// zenity and kdialog show, which leaves an empty space in the dialog.
+#[cfg(target_os = "linux")]
+pub fn supports_message_icon(typ: MessageType) -> bool {
+    crate::r#impl::gnu::message::supports_icon(typ)
+}
+
+/// Whether dialogs of type `typ` show their icon. Windows and macOS always have them.
+#[cfg(not(target_os = "linux"))]
+pub fn supports_message_icon(_typ: MessageType) -> bool {
+    true
+}
+
+/// Where the affirmative button goes next to the one that cancels, for apps laying out buttons of
+/// their own. It's the order the platform's guidelines call for, reading left to right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ButtonOrder {
+    /// "OK", "Cancel", as on Windows and KDE
+    AffirmativeFirst,
+    /// "Cancel", "OK", as on macOS, GNOME and other GTK desktops
+    AffirmativeLast,
+}
+
+/// The order the platform puts buttons in. On Linux it follows the backend that dialogs would be
+/// shown with, so buttons of the app's own line up with the crate's dialogs: KDE's order with
+/// kdialog and GNOME's with zenity. Without either, GNOME's order is the more common one.
+#[cfg(target_os = "linux")]
+pub fn platform_button_order() -> ButtonOrder {
+    crate::r#impl::gnu::message::button_order()
+}
+
+/// The order the platform puts buttons in.
+#[cfg(target_os = "macos")]
+pub fn platform_button_order() -> ButtonOrder {
+    ButtonOrder::AffirmativeLast
+}
+
+/// The order the platform puts buttons in.
+#[cfg(target_os = "windows")]
+pub fn platform_button_order() -> ButtonOrder {
+    ButtonOrder::AffirmativeFirst
+}
+
+// For `wrap_at`, since some backends let a single long line make the dialog as wide as the
+// screen. Lines are broken at the last space that keeps them within the column, and words longer
+// than a whole line are broken where the column falls. Line breaks already in the text are kept,
+// and markup counts toward the column like any other text.
+pub(crate) fn wrap_text(text: &str, column: Option<usize>) -> Cow<'_, str> {
+    let column = match column {
+        Some(column) if column > 0 => column,
+        _ => return Cow::Borrowed(text),
+    };
+
+    if text.lines().all(|line| line.chars().count() <= column) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut wrapped = String::with_capacity(text.len() + text.len() / column);
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            wrapped.push('\n');
+        }
+        wrap_line(line, column, &mut wrapped);
+    }
+    Cow::Owned(wrapped)
+}
+
+fn wrap_line(line: &str, column: usize, wrapped: &mut String) {
+    let mut width = 0;
+    for (i, word) in line.split(' ').enumerate() {
+        if i > 0 {
+            if width > 0 && width + 1 + word.chars().count() > column {
+                wrapped.push('\n');
+                width = 0;
+            } else {
+                wrapped.push(' ');
+                width += 1;
+            }
+        }
+
+        for c in word.chars() {
+            if width >= column {
+                wrapped.push('\n');
+                width = 0;
+            }
+            wrapped.push(c);
+            width += 1;
+        }
+    }
+}
+
+// Backends count in whole seconds, and a countdown that's already over still shows the dialog
+// for a moment.
+pub(crate) fn countdown_seconds(duration: Duration) -> u64 {
+    (duration.as_millis() as u64).div_ceil(1000).max(1)
+}
+
+// Used when `markup` is off on backends that interpret markup, so that user-supplied text can't
+// inject formatting.
+#[cfg(target_os = "linux")]
+pub(crate) fn escape_markup(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+// Backends that can only show plain text drop the tags when `markup` is on, so they don't end up
+// in the dialog.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn plain_text(text: &str, markup: bool) -> Cow<'_, str> {
+    match markup {
+        true => strip_markup(text),
+        false => Cow::Borrowed(text),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn strip_markup(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    let stripped = stripped
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+    Cow::Owned(stripped)
 }
@@ -0,0 +1,195 @@
+#[cfg(target_os = "windows")]
+use crate::ConfirmDetail;
+use crate::{
+    BusyHandle, Error, FontSelection, ItemKind, PrinterSelection, Result, SaveChangesChoice,
+};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+/// With the `mock` feature, dialogs never reach a backend. Where one would be called, the next
+/// response queued with `set_mock_responses` is taken instead, so code that shows dialogs can be
+/// tested without a display. Everything the crate does around the backend, like creating the
+/// starting folder or sorting and limiting a selection, still happens.
+#[derive(Debug)]
+pub enum MockResponse {
+    Alert,
+    Confirm(bool),
+    File(PathBuf),
+    Files(Vec<PathBuf>),
+    Item(PathBuf, ItemKind),
+    Text(String),
+    Form(Vec<String>),
+    Font(FontSelection),
+    Printer(PrinterSelection),
+    SaveChanges(SaveChangesChoice),
+    Cancel,
+    Error(Error),
+}
+
+static RESPONSES: Mutex<VecDeque<MockResponse>> = Mutex::new(VecDeque::new());
+
+/// Replaces the queued responses. There's one queue for the whole process, so dialogs shown with
+/// `spawn_thread` or `on_complete` take from it too. Tests that queue responses and run in
+/// parallel have to take turns, for example by holding a shared `Mutex` while they run.
+pub fn set_mock_responses(responses: Vec<MockResponse>) {
+    *lock_responses() = responses.into();
+}
+
+// A test that panicked while holding the queue doesn't leave it unusable for the others
+fn lock_responses() -> MutexGuard<'static, VecDeque<MockResponse>> {
+    RESPONSES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn respond<T: FromMock>(dialog: &'static str) -> Result<T> {
+    let response = lock_responses().pop_front();
+
+    match response {
+        Some(MockResponse::Error(error)) => Err(error),
+        Some(response) => {
+            let message = format!("mock response {:?} doesn't fit {}", response, dialog);
+            T::from_mock(response).ok_or(Error::ImplementationError(message))
+        }
+        None => Err(Error::ImplementationError(format!(
+            "no mock response left for {}",
+            dialog
+        ))),
+    }
+}
+
+// What a backend hands back, built from a mock response instead. `None` when the response doesn't
+// fit the dialog.
+pub(crate) trait FromMock: Sized {
+    fn from_mock(response: MockResponse) -> Option<Self>;
+}
+
+impl FromMock for () {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Alert | MockResponse::Cancel => Some(()),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for bool {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Confirm(answer) => Some(answer),
+            MockResponse::Cancel => Some(false),
+            _ => None,
+        }
+    }
+}
+
+// `AskOpenOrCreate`: `Some(true)` to open, `Some(false)` to create
+impl FromMock for Option<bool> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Confirm(answer) => Some(Some(answer)),
+            MockResponse::Cancel => Some(None),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl FromMock for ConfirmDetail {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        bool::from_mock(response).map(|answer| ConfirmDetail {
+            answer,
+            via_default: None,
+        })
+    }
+}
+
+impl FromMock for BusyHandle {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Alert => Some(BusyHandle { window: None }),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for SaveChangesChoice {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::SaveChanges(choice) => Some(choice),
+            MockResponse::Cancel => Some(SaveChangesChoice::Cancel),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for Option<PathBuf> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::File(path) => Some(Some(path)),
+            MockResponse::Cancel => Some(None),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for Vec<PathBuf> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Files(paths) => Some(paths),
+            MockResponse::File(path) => Some(vec![path]),
+            MockResponse::Cancel => Some(vec![]),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for Option<(PathBuf, ItemKind)> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Item(path, kind) => Some(Some((path, kind))),
+            MockResponse::File(path) => Some(Some((path, ItemKind::File))),
+            MockResponse::Cancel => Some(None),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for Option<String> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Text(text) => Some(Some(text)),
+            MockResponse::Cancel => Some(None),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for Option<Vec<String>> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Form(values) => Some(Some(values)),
+            MockResponse::Cancel => Some(None),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for Option<FontSelection> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Font(font) => Some(Some(font)),
+            MockResponse::Cancel => Some(None),
+            _ => None,
+        }
+    }
+}
+
+impl FromMock for Option<PrinterSelection> {
+    fn from_mock(response: MockResponse) -> Option<Self> {
+        match response {
+            MockResponse::Printer(printer) => Some(Some(printer)),
+            MockResponse::Cancel => Some(None),
+            _ => None,
+        }
+    }
+}
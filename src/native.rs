@@ -0,0 +1,19 @@
+use std::ffi::c_void;
+
+/// Unstable, and different on every platform. A `configure_native` callback gets one of these
+/// after the crate has set the dialog up and before it's shown, to change what the crate has no
+/// option for. Anything done with it is up to the caller to get right, and the handle mustn't be
+/// kept past the callback. Variants may be added or changed in any release.
+///
+/// The callback runs each time the dialog is shown, on the thread that shows it. It's `Sync` so
+/// that dialogs holding one can still be shown from another thread. macOS dialogs run in an
+/// osascript process the crate can't reach into, so it isn't called there.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum NativeDialogHandle {
+    /// The `IFileDialog*` of a Windows file dialog, which the crate still holds a reference to
+    FileDialog(*mut c_void),
+    /// The process ID of zenity or kdialog. The process is already running, so the dialog may
+    /// already be up.
+    Process(u32),
+}
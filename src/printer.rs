@@ -0,0 +1,16 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Windows and macOS have printer pickers. zenity and kdialog don't, and there's no GTK backend
+/// to show GTK's print dialog with, so on Linux the dialog fails with `Error::Unsupported`.
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PickPrinter {}
+
+/// `is_default` tells whether the chosen printer is the system's default one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PrinterSelection {
+    pub name: String,
+    pub is_default: bool,
+}
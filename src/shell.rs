@@ -0,0 +1,16 @@
+use crate::{r#impl::shell, Result};
+use std::path::Path;
+
+/// Opens the folder containing `path` in the system file manager, with `path` selected where the
+/// file manager supports it.
+pub fn reveal_in_file_manager(path: &Path) -> Result<()> {
+    let path = std::path::absolute(path)?;
+    std::fs::metadata(&path)?;
+
+    shell::reveal(&path)
+}
+
+/// Opens a file or URL with the application the system has registered for it.
+pub fn open_path(path: &Path) -> Result<()> {
+    shell::open(path)
+}
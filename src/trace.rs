@@ -0,0 +1,169 @@
+#[cfg(all(feature = "tracing", target_os = "windows"))]
+use crate::ConfirmDetail;
+#[cfg(feature = "tracing")]
+use crate::{BusyHandle, FontSelection, ItemKind, PrinterSelection, Result, SaveChangesChoice};
+#[cfg(feature = "tracing")]
+use std::path::PathBuf;
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn dialog<T>(dialog: &'static str, title: Option<&str>, f: impl FnOnce() -> T) -> T {
+    crate::hook::before_show(dialog, title);
+    f()
+}
+
+#[cfg(all(not(feature = "tracing"), target_os = "linux"))]
+#[inline(always)]
+pub(crate) fn backend(_backend: &'static str) {}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn dialog<T: Outcome>(
+    dialog: &'static str,
+    title: Option<&str>,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    use tracing::field::Empty;
+
+    let span = tracing::info_span!("dialog", dialog, backend = Empty, outcome = Empty);
+    let _enter = span.enter();
+
+    crate::hook::before_show(dialog, title);
+
+    #[cfg(feature = "mock")]
+    span.record("backend", "mock");
+    #[cfg(all(not(feature = "mock"), target_os = "macos"))]
+    span.record("backend", "osascript");
+    #[cfg(all(not(feature = "mock"), target_os = "windows"))]
+    span.record("backend", "win32");
+
+    let result = f();
+
+    match &result {
+        Ok(output) if output.is_cancelled() => span.record("outcome", "cancelled"),
+        Ok(_) => span.record("outcome", "completed"),
+        Err(error) => {
+            tracing::debug!(%error, "dialog failed");
+            span.record("outcome", "error")
+        }
+    };
+
+    result
+}
+
+#[cfg(all(feature = "tracing", target_os = "linux"))]
+pub(crate) fn backend(backend: &'static str) {
+    tracing::Span::current().record("backend", backend);
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) trait Outcome: Sized {
+    fn is_cancelled(&self) -> bool;
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for () {
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for bool {
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+// `AskOpenOrCreate`: `Some(true)` to open, `Some(false)` to create
+#[cfg(feature = "tracing")]
+impl Outcome for Option<bool> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(all(feature = "tracing", target_os = "windows"))]
+impl Outcome for ConfirmDetail {
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+// Shown, but not yet closed
+#[cfg(feature = "tracing")]
+impl Outcome for BusyHandle {
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for SaveChangesChoice {
+    fn is_cancelled(&self) -> bool {
+        *self == SaveChangesChoice::Cancel
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Option<PathBuf> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Vec<PathBuf> {
+    fn is_cancelled(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+// With the shortcut that led to the file, if one was resolved
+#[cfg(feature = "tracing")]
+impl Outcome for Option<(PathBuf, Option<PathBuf>)> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Vec<(PathBuf, Option<PathBuf>)> {
+    fn is_cancelled(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Option<(PathBuf, ItemKind)> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Option<String> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Option<Vec<String>> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Option<FontSelection> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Option<PrinterSelection> {
+    fn is_cancelled(&self) -> bool {
+        self.is_none()
+    }
+}
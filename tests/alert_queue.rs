@@ -0,0 +1,64 @@
+// zenity is replaced by a shell script that takes a while to answer, so this runs without a
+// display or zenity installed.
+#![cfg(all(target_os = "linux", not(feature = "mock")))]
+
+use native_dialog::*;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn serialized_alerts_show_one_at_a_time_in_order() {
+    let dir = env::temp_dir().join(format!("native-dialog-queue-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let log = dir.join("log");
+    let zenity = dir.join("zenity.sh");
+    let script = format!(
+        "#!/bin/sh\nfor arg; do case $arg in alert-*) name=$arg;; esac; done\n\
+         echo \"open $name\" >> {log}\nsleep 0.2\necho \"close $name\" >> {log}\n",
+        log = log.display(),
+    );
+    fs::write(&zenity, script).unwrap();
+    fs::set_permissions(&zenity, fs::Permissions::from_mode(0o755)).unwrap();
+
+    env::set_var("DISPLAY", ":0");
+    env::set_var("XDG_CURRENT_DESKTOP", "GNOME");
+    set_backend_path(Backend::Zenity, zenity);
+    set_serialize_dialogs(true);
+
+    let threads: Vec<_> = (0..3)
+        .map(|i| {
+            thread::spawn(move || {
+                // Staggered, so each alert is shown while the one before is still open
+                thread::sleep(Duration::from_millis(50 * i));
+                let text = format!("alert-{}", i);
+                MessageAlert {
+                    text: &text,
+                    ..Default::default()
+                }
+                .show()
+                .unwrap();
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let log = fs::read_to_string(&log).unwrap();
+    let expected = [
+        "open alert-0",
+        "close alert-0",
+        "open alert-1",
+        "close alert-1",
+        "open alert-2",
+        "close alert-2",
+    ];
+    assert_eq!(log.lines().collect::<Vec<_>>(), expected);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,33 @@
+// zenity is replaced by a shell script that answers each entry with its starting text, so this
+// runs without a display or zenity installed.
+#![cfg(all(target_os = "linux", not(feature = "mock")))]
+
+use native_dialog::*;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn zenity_form_keeps_defaults() {
+    let dir = env::temp_dir().join(format!("native-dialog-form-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let zenity = dir.join("zenity.sh");
+    let script = "#!/bin/sh\n\
+                  while [ $# -gt 0 ]; do [ \"$1\" = --entry-text ] && echo \"$2\"; shift; done\n";
+    fs::write(&zenity, script).unwrap();
+    fs::set_permissions(&zenity, fs::Permissions::from_mode(0o755)).unwrap();
+
+    env::set_var("DISPLAY", ":0");
+    env::set_var("XDG_CURRENT_DESKTOP", "GNOME");
+    set_backend_path(Backend::Zenity, zenity);
+
+    let values = Form::default()
+        .add_field("User", "admin")
+        .add_field("Server", "")
+        .show()
+        .unwrap();
+    assert_eq!(values, Some(vec!["admin".to_string(), String::new()]));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
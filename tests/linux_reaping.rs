@@ -0,0 +1,84 @@
+// zenity and kdialog are replaced by shell scripts, so this runs without a display or either tool
+// installed. With the `mock` feature no process is spawned at all.
+#![cfg(all(target_os = "linux", not(feature = "mock")))]
+
+use native_dialog::*;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+fn stub(dir: &Path, name: &str, script: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+// Children of this process that exited without being waited on
+fn zombie_children() -> Vec<u32> {
+    let me = std::process::id();
+
+    fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| {
+            let stat = match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+                Ok(stat) => stat,
+                Err(_) => return false,
+            };
+            // The command name is in parentheses and may contain spaces, so the fields are
+            // counted from the last one
+            let fields: Vec<&str> = stat[stat.rfind(')').unwrap() + 1..]
+                .split_whitespace()
+                .collect();
+            fields[0] == "Z" && fields[1] == me.to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn no_zombies_are_left_behind() {
+    let dir = env::temp_dir().join(format!("native-dialog-reaping-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let exits = stub(&dir, "exits.sh", "echo /tmp/picked.txt");
+    let hangs = stub(&dir, "hangs.sh", "exec sleep 30");
+
+    env::set_var("DISPLAY", ":0");
+    env::set_var("XDG_CURRENT_DESKTOP", "GNOME");
+
+    set_backend_path(Backend::Zenity, exits);
+    for _ in 0..20 {
+        let picked = OpenSingleFile::default().show().unwrap();
+        assert_eq!(picked, Some(PathBuf::from("/tmp/picked.txt")));
+    }
+
+    let workers: Vec<_> = (0..10)
+        .map(|_| MessageAlert::default().spawn_thread())
+        .collect();
+    for worker in workers {
+        worker.join().unwrap().unwrap();
+    }
+
+    set_backend_path(Backend::Zenity, hangs);
+    for _ in 0..10 {
+        let dialog = MessageAlert {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        assert!(matches!(dialog.show(), Err(Error::Timeout(_))));
+    }
+
+    for _ in 0..5 {
+        let busy = Busy {
+            title: "Working",
+            text: "Please wait",
+        };
+        drop(busy.show().unwrap());
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(zombie_children(), Vec::<u32>::new());
+}
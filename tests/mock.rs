@@ -0,0 +1,175 @@
+// Mock responses only stand in for the backend, so what the crate does around it still happens.
+#![cfg(feature = "mock")]
+
+use native_dialog::*;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex, MutexGuard};
+
+// The response queue is shared by the whole process, so the tests take turns with it
+fn queue() -> MutexGuard<'static, ()> {
+    static QUEUE: Mutex<()> = Mutex::new(());
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("native-dialog-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn selection_is_sorted_and_limited() {
+    let _queue = queue();
+    set_mock_responses(vec![MockResponse::Files(vec![
+        PathBuf::from("/c.txt"),
+        PathBuf::from("/a.txt"),
+        PathBuf::from("/b.txt"),
+    ])]);
+
+    let paths = OpenMultipleFile::default()
+        .max_selection(2)
+        .truncate_selection(true)
+        .sort_results(SortOrder::FileName)
+        .show()
+        .unwrap();
+
+    assert_eq!(paths, [PathBuf::from("/a.txt"), PathBuf::from("/b.txt")]);
+}
+
+#[test]
+fn too_many_selected_is_an_error() {
+    let _queue = queue();
+    set_mock_responses(vec![MockResponse::Files(vec![
+        PathBuf::from("/a.txt"),
+        PathBuf::from("/b.txt"),
+    ])]);
+
+    let result = OpenMultipleFile::default().max_selection(1).show();
+
+    assert!(matches!(result, Err(Error::TooManySelected(2))));
+}
+
+#[test]
+fn missing_dir_is_created() {
+    let _queue = queue();
+    let dir = temp_dir("create");
+    set_mock_responses(vec![MockResponse::Cancel]);
+
+    let path = OpenSingleFile {
+        dir: dir.to_str(),
+        ..Default::default()
+    }
+    .create_dir_if_missing(true)
+    .show()
+    .unwrap();
+
+    assert_eq!(path, None);
+    assert!(dir.is_dir());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn single_match_skips_the_backend() {
+    let _queue = queue();
+    let dir = temp_dir("single");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("only.txt"), "").unwrap();
+    fs::write(dir.join("other.md"), "").unwrap();
+    set_mock_responses(vec![MockResponse::File(PathBuf::from("/unused.txt"))]);
+
+    let path = OpenSingleFile {
+        dir: dir.to_str(),
+        filter: Some(&["txt"]),
+        ..Default::default()
+    }
+    .auto_select_single(true)
+    .show()
+    .unwrap();
+
+    assert_eq!(path, Some(dir.join("only.txt")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn mismatched_response_is_an_error() {
+    let _queue = queue();
+    set_mock_responses(vec![MockResponse::Text("hello".to_string())]);
+
+    let result = OpenSingleFile::default().show();
+
+    assert!(matches!(result, Err(Error::ImplementationError(_))));
+}
+
+#[cfg(unix)]
+#[test]
+fn shortcuts_are_paired_with_their_targets() {
+    let _queue = queue();
+    let dir = temp_dir("shortcuts");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("target.txt"), "").unwrap();
+    std::os::unix::fs::symlink(dir.join("target.txt"), dir.join("link.txt")).unwrap();
+
+    // Resolved by a plain `show` first, which must not leave the link behind for the next call
+    set_mock_responses(vec![
+        MockResponse::File(dir.join("link.txt")),
+        MockResponse::File(dir.join("target.txt")),
+        MockResponse::File(dir.join("link.txt")),
+    ]);
+    let dialog = || OpenSingleFile::default().resolve_shortcuts(true);
+    assert_eq!(dialog().show().unwrap(), Some(dir.join("target.txt")));
+
+    let plain = dialog().show_with_shortcuts().unwrap();
+    assert_eq!(plain, Some((dir.join("target.txt"), None)));
+
+    let linked = dialog().show_with_shortcuts().unwrap();
+    assert_eq!(
+        linked,
+        Some((dir.join("target.txt"), Some(dir.join("link.txt"))))
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unsupported_url_fails_before_the_backend() {
+    let _queue = queue();
+    set_mock_responses(vec![MockResponse::Cancel]);
+
+    let result = OpenSingleFile::default()
+        .set_location_url("https://example.com/files")
+        .show();
+    assert!(matches!(result, Err(Error::UnsupportedUrl(_))));
+
+    // The response is still there for the next dialog
+    assert_eq!(OpenSingleFile::default().show().unwrap(), None);
+}
+
+#[test]
+fn worker_threads_take_from_the_same_queue() {
+    let _queue = queue();
+    set_mock_responses(vec![
+        MockResponse::Confirm(true),
+        MockResponse::File(PathBuf::from("/picked.txt")),
+    ]);
+
+    let confirmed = MessageConfirm::default().spawn_thread().join().unwrap();
+    assert!(confirmed.unwrap());
+
+    let (sender, receiver) = mpsc::channel();
+    OpenSingleFile::default().on_complete(move |result| sender.send(result).unwrap());
+    let path = receiver.recv().unwrap().unwrap();
+    assert_eq!(path, Some(PathBuf::from("/picked.txt")));
+}
+
+#[test]
+fn empty_form_shows_nothing() {
+    let _queue = queue();
+    set_mock_responses(vec![MockResponse::Cancel]);
+
+    assert_eq!(Form::default().show().unwrap(), Some(vec![]));
+
+    // The response is still there for the next dialog
+    assert_eq!(OpenSingleFile::default().show().unwrap(), None);
+}
@@ -0,0 +1,34 @@
+// xdg-open is replaced by shell scripts, so this runs without a desktop to open anything in.
+#![cfg(all(target_os = "linux", not(feature = "mock")))]
+
+use native_dialog::*;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+fn stub(dir: &Path, name: &str, script: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+fn open_returns_without_waiting_for_the_application() {
+    let dir = env::temp_dir().join(format!("native-dialog-open-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let runs = stub(&dir, "runs.sh", "exec sleep 30");
+    let no_handler = stub(&dir, "no-handler.sh", "exit 3");
+
+    set_backend_path(Backend::XdgOpen, runs);
+    let start = Instant::now();
+    open_path(&dir).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    set_backend_path(Backend::XdgOpen, no_handler);
+    assert!(matches!(open_path(&dir), Err(Error::NoHandler)));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
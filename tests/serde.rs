@@ -0,0 +1,70 @@
+#![cfg(feature = "serde")]
+
+use native_dialog::*;
+
+#[test]
+fn file_dialog_round_trips() {
+    let spec = DialogSpec::OpenSingleFile {
+        dir: Some("C:\\Users\\me\\Pictures".to_string()),
+        filter: Some(vec!["txt".to_string(), "md".to_string()]),
+        filters: vec![(
+            "Images".to_string(),
+            vec!["png".to_string(), "jpg".to_string()],
+        )],
+    };
+
+    let json = serde_json::to_string(&spec).unwrap();
+    let back: DialogSpec = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(back, spec);
+}
+
+#[test]
+fn escaped_strings_are_read() {
+    let json = r#"{
+        "MessageAlert": {
+            "title": "Can't open \"report.csv\"",
+            "text": "The file is locked.\nTry again later.",
+            "typ": "Error"
+        }
+    }"#;
+
+    let spec: DialogSpec = serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+        spec,
+        DialogSpec::MessageAlert {
+            title: "Can't open \"report.csv\"".to_string(),
+            text: "The file is locked.\nTry again later.".to_string(),
+            typ: MessageType::Error,
+        }
+    );
+    assert_eq!(
+        serde_json::from_str::<DialogSpec>(&serde_json::to_string(&spec).unwrap()).unwrap(),
+        spec
+    );
+}
+
+#[test]
+fn filters_default_to_none() {
+    let json = r#"{ "SaveFile": { "dir": "D:\\Reports", "name": "report.csv" } }"#;
+
+    let spec: DialogSpec = serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+        spec,
+        DialogSpec::SaveFile {
+            dir: Some("D:\\Reports".to_string()),
+            name: "report.csv".to_string(),
+            filters: vec![],
+        }
+    );
+}
+
+#[test]
+fn message_type_round_trips() {
+    for typ in [MessageType::Info, MessageType::Warning, MessageType::Error] {
+        let json = serde_json::to_string(&typ).unwrap();
+        assert_eq!(serde_json::from_str::<MessageType>(&json).unwrap(), typ);
+    }
+}